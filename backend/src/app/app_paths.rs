@@ -35,6 +35,12 @@ pub fn app_data() -> PathBuf {
     })
 }
 
+pub fn traces_dir() -> PathBuf {
+  std::env::var("LENZ_TRACES_DIR")
+    .map(PathBuf::from)
+    .unwrap_or_else(|_| app_data().join("traces"))
+}
+
 pub fn built_in_extensions() -> PathBuf {
   std::env::var("LENZ_BUILT_IN_EXTENSIONS_PATH")
     .map(PathBuf::from)
@@ -91,3 +97,9 @@ pub fn extensions_search_paths() -> Vec<PathBuf> {
       .collect()
   }
 }
+
+pub fn window_state() -> PathBuf {
+  std::env::var("LENZ_WINDOW_STATE_PATH")
+    .map(PathBuf::from)
+    .unwrap_or_else(|_| app_data().join("window-state.json"))
+}
@@ -0,0 +1,501 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use rand::{distributions::Alphanumeric, Rng};
+use tokio::{
+  io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+  net::{TcpListener, TcpStream},
+  sync::{mpsc, oneshot},
+};
+use wry::http::{Request, Response, Uri};
+
+use super::{
+  invoke::{handle_ipc_request, IpcResponder, MAX_INVOKE_BODY_SIZE},
+  App,
+};
+
+pub const DEFAULT_BRIDGE_PORT: u16 = 7420;
+
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+const TOKEN_LENGTH: usize = 32;
+
+/// Settings of a running bridge. The token is generated per launch and only
+/// handed to pages of `origin`, through `/init.js`.
+struct BridgeConfig {
+  port: u16,
+  origin: String,
+  token: String,
+}
+
+/// A browser connected to `/events`, receiving the events of `window_id`.
+pub struct BridgeClient {
+  window_id: u32,
+  sender: mpsc::UnboundedSender<String>,
+}
+
+/// Serves the IPC protocol over plain HTTP on localhost, so the UI can run in
+/// a regular browser during development:
+///
+/// - `POST /ipc/<host>/<path>` is handled like `ipc://<host>/<path>`;
+/// - `GET /events?window=<id>&token=<token>` streams the window's events as
+///   Server-Sent Events;
+/// - `GET /init.js` defines the import map and `window.LENZ_BRIDGE`.
+///
+/// Only pages served from the origin of `base_url` are allowed: `/init.js` is
+/// refused to other referrers, requests from other origins are rejected, and
+/// IPC requests must carry the token of this launch in `X-Lenz-Token`.
+///
+/// Requests without `X-Window-Id` act on behalf of the main window.
+pub fn start(app: App, port: u16, base_url: &str) {
+  let address = SocketAddr::from(([127, 0, 0, 1], port));
+  let config = Arc::new(BridgeConfig {
+    port,
+    origin: origin_of(base_url),
+    token: rand::thread_rng()
+      .sample_iter(&Alphanumeric)
+      .take(TOKEN_LENGTH)
+      .map(char::from)
+      .collect(),
+  });
+
+  tokio::task::spawn(async move {
+    let listener = match TcpListener::bind(address).await {
+      Ok(listener) => listener,
+      Err(err) => {
+        eprintln!("Failed to start IPC bridge on {} > {}", address, err);
+        return;
+      }
+    };
+
+    println!("IPC bridge listening on http://{}", address);
+
+    loop {
+      match listener.accept().await {
+        Ok((stream, _)) => {
+          tokio::task::spawn(handle_connection(app.clone(), stream, config.clone()));
+        }
+        Err(err) => eprintln!("Failed to accept IPC bridge connection > {}", err),
+      }
+    }
+  });
+}
+
+/// Forwards an event dispatched to `target` windows to the connected browsers.
+pub fn dispatch(app: &App, name: &str, payload: &serde_json::Value, target: &[u32]) {
+  let message = serde_json::json!({ "name": name, "payload": payload }).to_string();
+
+  app
+    .bridge_clients
+    .write()
+    .expect("Bridge clients lock is poisoned")
+    .retain(|client| {
+      !target.contains(&client.window_id) || client.sender.send(message.clone()).is_ok()
+    });
+}
+
+async fn handle_connection(app: App, stream: TcpStream, config: Arc<BridgeConfig>) {
+  let mut stream = BufReader::new(stream);
+  let (request, content_length) = match read_request(&mut stream).await {
+    Ok(request) => request,
+    Err((status, err)) => {
+      let _ = write_response(&mut stream, error_response(status, err)).await;
+      return;
+    }
+  };
+  let path = request.uri().path().to_string();
+
+  if let Err(err) = check_origin(&request, &config) {
+    let _ = write_response(&mut stream, forbidden(err)).await;
+    return;
+  }
+
+  if request.method() == "OPTIONS" {
+    let _ = write_response(
+      &mut stream,
+      Response::builder()
+        .status(204)
+        .header("Access-Control-Allow-Origin", &config.origin)
+        .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
+        .header(
+          "Access-Control-Allow-Headers",
+          "X-Window-Id,X-Request-Id,X-Lenz-Token,Content-Type",
+        )
+        .header("Vary", "Origin")
+        .body(Vec::new())
+        .unwrap(),
+    )
+    .await;
+    return;
+  }
+
+  if request.method() == "GET" && path == "/init.js" {
+    // Loaded with a classic `<script>`, which sends no `Origin`. The referrer
+    // keeps other sites from including it and reading the token.
+    let referrer = header(&request, "Referer").map(origin_of);
+
+    let response = if referrer.as_deref() == Some(config.origin.as_str()) {
+      Response::builder()
+        .header("Content-Type", "text/javascript")
+        .header("Cache-Control", "no-store")
+        .body(init_script(&app, &config).into_bytes())
+        .unwrap()
+    } else {
+      forbidden("init.js can only be loaded by the app")
+    };
+
+    let _ = write_response(&mut stream, response).await;
+    return;
+  }
+
+  if request.method() == "GET" && path == "/events" {
+    // `EventSource` can't send headers, so the token comes in the query.
+    if query_param(&request, "token") != Some(config.token.as_str()) {
+      let _ = write_response(&mut stream, forbidden("Invalid bridge token")).await;
+      return;
+    }
+
+    let window_id = query_param(&request, "window")
+      .and_then(|id| id.parse().ok())
+      .or_else(|| main_window_id(&app));
+
+    return match window_id {
+      Some(window_id) => stream_events(&app, stream.into_inner(), window_id, &config).await,
+      None => {
+        let _ = write_response(
+          &mut stream,
+          Response::builder()
+            .status(404)
+            .body(b"Window not found".to_vec())
+            .unwrap(),
+        )
+        .await;
+      }
+    };
+  }
+
+  if let Some(ipc_path) = path.strip_prefix("/ipc/") {
+    if header(&request, "X-Lenz-Token") != Some(config.token.as_str()) {
+      let _ = write_response(&mut stream, forbidden("Invalid bridge token")).await;
+      return;
+    }
+
+    // Read only once the request is allowed, and grown with the data actually
+    // sent rather than allocated from `Content-Length`.
+    let mut body = Vec::new();
+
+    if let Err(err) = (&mut stream)
+      .take(content_length as u64)
+      .read_to_end(&mut body)
+      .await
+    {
+      let _ = write_response(&mut stream, error_response(400, err.to_string())).await;
+      return;
+    }
+
+    if body.len() < content_length {
+      let _ = write_response(&mut stream, error_response(400, "Body is incomplete")).await;
+      return;
+    }
+
+    let (response_sender, response_receiver) = oneshot::channel();
+    let (mut parts, _) = request.into_parts();
+
+    if !parts.headers.contains_key("X-Window-Id") {
+      if let Some(window_id) = main_window_id(&app) {
+        parts
+          .headers
+          .insert("X-Window-Id", window_id.to_string().parse().unwrap());
+      }
+    }
+
+    parts.uri = match format!("ipc://{}", ipc_path).parse() {
+      Ok(uri) => uri,
+      Err(_) => {
+        let _ = write_response(&mut stream, error_response(400, "Invalid IPC path")).await;
+        return;
+      }
+    };
+
+    handle_ipc_request(
+      app,
+      Request::from_parts(parts, body),
+      IpcResponder::new(move |response| {
+        let _ = response_sender.send(response);
+      }),
+    );
+
+    if let Ok(mut response) = response_receiver.await {
+      // The protocol allows any origin, the bridge only the app's.
+      response.headers_mut().insert(
+        "Access-Control-Allow-Origin",
+        config.origin.parse().unwrap(),
+      );
+      let _ = write_response(&mut stream, response).await;
+    }
+
+    return;
+  }
+
+  let _ = write_response(
+    &mut stream,
+    Response::builder()
+      .status(404)
+      .body(b"Not found".to_vec())
+      .unwrap(),
+  )
+  .await;
+}
+
+/// Rejects requests from other sites, and requests addressed to another host
+/// name that resolves to localhost (DNS rebinding).
+fn check_origin(request: &Request<Vec<u8>>, config: &BridgeConfig) -> Result<(), &'static str> {
+  let host = header(request, "Host").unwrap_or_default();
+
+  if host != format!("127.0.0.1:{}", config.port) && host != format!("localhost:{}", config.port) {
+    return Err("Invalid host");
+  }
+
+  match header(request, "Origin") {
+    Some(origin) if origin != config.origin => Err("Origin not allowed"),
+    _ => Ok(()),
+  }
+}
+
+/// `scheme://authority` of a URL, or the URL itself when it can't be parsed.
+fn origin_of(url: &str) -> String {
+  match url.parse::<Uri>() {
+    Ok(uri) => match (uri.scheme_str(), uri.authority()) {
+      (Some(scheme), Some(authority)) => format!("{}://{}", scheme, authority),
+      _ => url.trim_end_matches('/').to_string(),
+    },
+    Err(_) => url.trim_end_matches('/').to_string(),
+  }
+}
+
+fn header<'a>(request: &'a Request<Vec<u8>>, name: &str) -> Option<&'a str> {
+  request
+    .headers()
+    .get(name)
+    .and_then(|value| value.to_str().ok())
+}
+
+fn query_param<'a>(request: &'a Request<Vec<u8>>, name: &str) -> Option<&'a str> {
+  request.uri().query()?.split('&').find_map(|param| {
+    param
+      .split_once('=')
+      .filter(|(key, _)| *key == name)
+      .map(|(_, value)| value)
+  })
+}
+
+fn forbidden(message: &str) -> Response<Vec<u8>> {
+  error_response(403, message)
+}
+
+fn error_response(status: u16, message: impl Into<String>) -> Response<Vec<u8>> {
+  Response::builder()
+    .status(status)
+    .body(message.into().into_bytes())
+    .unwrap()
+}
+
+fn main_window_id(app: &App) -> Option<u32> {
+  *app
+    .main_window_id
+    .read()
+    .expect("Main window id lock is poisoned")
+}
+
+async fn stream_events(app: &App, mut stream: TcpStream, window_id: u32, config: &BridgeConfig) {
+  let (sender, mut receiver) = mpsc::unbounded_channel();
+  let head = format!(
+    "HTTP/1.1 200 OK\r\n\
+    Content-Type: text/event-stream\r\n\
+    Cache-Control: no-cache\r\n\
+    Access-Control-Allow-Origin: {}\r\n\
+    Connection: keep-alive\r\n\r\n",
+    config.origin
+  );
+
+  if stream.write_all(head.as_bytes()).await.is_err() {
+    return;
+  }
+
+  let _client = ClientGuard::register(app, window_id, sender);
+  let (mut reader, mut writer) = stream.split();
+  let mut closed = [0; 1];
+
+  loop {
+    tokio::select! {
+      message = receiver.recv() => match message {
+        Some(message) => {
+          if writer
+            .write_all(format!("data: {}\n\n", message).as_bytes())
+            .await
+            .is_err()
+          {
+            break;
+          }
+        }
+        None => break,
+      },
+      // Browsers send nothing after the request, so this only returns once
+      // the connection is closed.
+      _ = reader.read(&mut closed) => break,
+    }
+  }
+}
+
+/// Keeps a client in `bridge_clients` while its event stream is open.
+struct ClientGuard<'a> {
+  app: &'a App,
+  sender: mpsc::UnboundedSender<String>,
+}
+
+impl<'a> ClientGuard<'a> {
+  fn register(app: &'a App, window_id: u32, sender: mpsc::UnboundedSender<String>) -> Self {
+    app
+      .bridge_clients
+      .write()
+      .expect("Bridge clients lock is poisoned")
+      .push(BridgeClient {
+        window_id,
+        sender: sender.clone(),
+      });
+
+    Self { app, sender }
+  }
+}
+
+impl Drop for ClientGuard<'_> {
+  fn drop(&mut self) {
+    self
+      .app
+      .bridge_clients
+      .write()
+      .expect("Bridge clients lock is poisoned")
+      .retain(|client| !client.sender.same_channel(&self.sender));
+  }
+}
+
+fn init_script(app: &App, config: &BridgeConfig) -> String {
+  let import_map = app
+    .import_map
+    .read()
+    .expect("Failed to acquire lock on import map")
+    .clone();
+
+  format!(
+    r#"(() => {{
+  Object.defineProperty(window, 'LENZ_BRIDGE', {{
+    value: Object.freeze({{ url: 'http://127.0.0.1:{port}', windowId: {window_id}, token: '{token}' }}),
+  }});
+
+  const script = document.createElement('script');
+
+  script.type = 'importmap';
+  script.textContent = JSON.stringify({{ imports: {import_map} }});
+  document.currentScript.after(script);
+}})();
+"#,
+    port = config.port,
+    token = config.token,
+    window_id = main_window_id(app).map_or("null".to_string(), |id| id.to_string()),
+    import_map = serde_json::to_string(&import_map).expect("Failed to serialize import map"),
+  )
+}
+
+/// Reads the request line and headers, and returns the request without its
+/// body along with its `Content-Length`. The body is left in `stream`, so it
+/// is only read for allowed requests.
+///
+/// Fails with the status to reply with and a message.
+async fn read_request(
+  stream: &mut BufReader<TcpStream>,
+) -> Result<(Request<Vec<u8>>, usize), (u16, String)> {
+  let mut line = String::new();
+  let mut header_size = 0;
+
+  stream
+    .read_line(&mut line)
+    .await
+    .map_err(|err| (400, err.to_string()))?;
+
+  let mut request_line = line.split_whitespace();
+  let method = request_line
+    .next()
+    .ok_or((400, "Missing method".to_string()))?
+    .to_string();
+  let uri = request_line
+    .next()
+    .ok_or((400, "Missing path".to_string()))?
+    .to_string();
+  let mut builder = Request::builder().method(method.as_str()).uri(uri.as_str());
+  let mut content_length = 0;
+
+  loop {
+    line.clear();
+    header_size += stream
+      .read_line(&mut line)
+      .await
+      .map_err(|err| (400, err.to_string()))?;
+
+    if header_size > MAX_HEADER_SIZE {
+      return Err((431, "Headers are too large".to_string()));
+    }
+
+    let header = line.trim_end();
+
+    if header.is_empty() {
+      break;
+    }
+
+    let (name, value) = header
+      .split_once(':')
+      .ok_or((400, "Invalid header".to_string()))?;
+    let value = value.trim();
+
+    if name.eq_ignore_ascii_case("Content-Length") {
+      content_length = value
+        .parse()
+        .map_err(|_| (400, "Invalid Content-Length".to_string()))?;
+
+      if content_length > MAX_INVOKE_BODY_SIZE {
+        return Err((413, "Body is too large".to_string()));
+      }
+    }
+
+    builder = builder.header(name, value);
+  }
+
+  builder
+    .body(Vec::new())
+    .map(|request| (request, content_length))
+    .map_err(|err| (400, err.to_string()))
+}
+
+async fn write_response<W: AsyncWrite + Unpin>(
+  stream: &mut W,
+  response: Response<Vec<u8>>,
+) -> std::io::Result<()> {
+  let (parts, body) = response.into_parts();
+  let mut head = format!(
+    "HTTP/1.1 {} {}\r\n",
+    parts.status.as_u16(),
+    parts.status.canonical_reason().unwrap_or_default()
+  );
+
+  for (name, value) in parts.headers.iter() {
+    if let Ok(value) = value.to_str() {
+      head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+  }
+
+  head.push_str(&format!(
+    "Content-Length: {}\r\nConnection: close\r\n\r\n",
+    body.len()
+  ));
+
+  stream.write_all(head.as_bytes()).await?;
+  stream.write_all(&body).await?;
+  stream.shutdown().await
+}
@@ -0,0 +1,171 @@
+use std::{
+  marker::PhantomData,
+  sync::atomic::{AtomicU32, Ordering},
+};
+
+use serde::Serialize;
+use serde_json::json;
+
+use super::{
+  invoke::{CancellationToken, InvokeResult},
+  window::{AppWindow, AppWindowEvent, AppWindowExt},
+  App,
+};
+
+/// Channel ids are returned to the webview as `{"$channel": <id>}`.
+pub const CHANNEL_KEY: &str = "$channel";
+
+static NEXT_CHANNEL_ID: AtomicU32 = AtomicU32::new(1);
+
+pub struct ChannelEntry {
+  window_id: u32,
+  closed: CancellationToken,
+}
+
+/// Pushes messages to the window that subscribed to it. The channel is closed
+/// when the window calls `unsubscribe`, when the window is closed or when the
+/// producer calls `close`.
+pub struct Channel<T = serde_json::Value> {
+  id: u32,
+  window_id: u32,
+  app: App,
+  closed: CancellationToken,
+  _message: PhantomData<fn(T)>,
+}
+
+impl<T> Clone for Channel<T> {
+  fn clone(&self) -> Self {
+    Self {
+      id: self.id,
+      window_id: self.window_id,
+      app: self.app.clone(),
+      closed: self.closed.clone(),
+      _message: PhantomData,
+    }
+  }
+}
+
+impl<T: Serialize> Channel<T> {
+  pub fn open(window: &AppWindow) -> Self {
+    let id = NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed);
+    let app = window.app();
+    let closed = CancellationToken::new();
+
+    app
+      .channels
+      .write()
+      .expect("Channels lock is poisoned")
+      .insert(
+        id,
+        ChannelEntry {
+          window_id: window.id(),
+          closed: closed.clone(),
+        },
+      );
+
+    Self {
+      id,
+      window_id: window.id(),
+      app,
+      closed,
+      _message: PhantomData,
+    }
+  }
+
+  pub fn is_open(&self) -> bool {
+    self
+      .app
+      .channels
+      .read()
+      .expect("Channels lock is poisoned")
+      .contains_key(&self.id)
+  }
+
+  /// Returns `false` once the channel is closed, so producers know to stop.
+  pub fn send(&self, message: T) -> bool {
+    if !self.is_open() {
+      return false;
+    }
+
+    match serde_json::to_value(message) {
+      Ok(message) => self.dispatch(json!({ "id": self.id, "message": message })),
+      Err(err) => {
+        eprintln!("Failed to serialize message for channel {} > {}", self.id, err);
+        true
+      }
+    }
+  }
+
+  pub async fn closed(&self) {
+    self.closed.cancelled().await
+  }
+
+  pub fn close(&self) {
+    if close_channel(&self.app, self.id) {
+      self.dispatch(json!({ "id": self.id, "closed": true }));
+    }
+  }
+
+  fn dispatch(&self, payload: serde_json::Value) -> bool {
+    self
+      .app
+      .event_loop_proxy
+      .send_event(AppWindowEvent::Event {
+        name: "ipc.channel".to_string(),
+        payload,
+        target: vec![self.window_id],
+      })
+      .is_ok()
+  }
+}
+
+impl<T> From<Channel<T>> for InvokeResult {
+  fn from(channel: Channel<T>) -> Self {
+    InvokeResult::json(json!({ CHANNEL_KEY: channel.id }))
+  }
+}
+
+pub fn close_channel(app: &App, id: u32) -> bool {
+  let entry = app
+    .channels
+    .write()
+    .expect("Channels lock is poisoned")
+    .remove(&id);
+
+  match entry {
+    Some(entry) => {
+      entry.closed.cancel();
+      true
+    }
+    None => false,
+  }
+}
+
+/// Closes a channel on behalf of the window that owns it.
+pub fn unsubscribe(app: &App, window_id: u32, id: u32) -> bool {
+  let is_owner = app
+    .channels
+    .read()
+    .expect("Channels lock is poisoned")
+    .get(&id)
+    .is_some_and(|entry| entry.window_id == window_id);
+
+  is_owner && close_channel(app, id)
+}
+
+/// Closes the channels of a window that was closed or started loading another
+/// page, which stops their producers.
+pub fn close_window_channels(app: &App, window_id: u32) {
+  let ids: Vec<u32> = app
+    .channels
+    .read()
+    .expect("Channels lock is poisoned")
+    .iter()
+    .filter(|(_, entry)| entry.window_id == window_id)
+    .map(|(id, _)| *id)
+    .collect();
+
+  for id in ids {
+    close_channel(app, id);
+  }
+}
@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use super::{
+  window::{descendant_ids, AppWindow, AppWindowEvent, AppWindowExt},
+  App, AppExt,
+};
+
+/// How long a page can take to answer a close request, which includes the
+/// time the user spends on a confirmation dialog.
+const CLOSE_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Asks the page before closing a window the user tried to close. Closing the
+/// main window quits the app, so every window is asked with `app.before_quit`
+/// instead of `window.close_requested`, which also goes to the children that
+/// would close with the window. The window closes unless a page prevents it;
+/// pages that fail, time out or have no handler don't.
+pub fn request_close(app: App, window_id: u32) {
+  // A window blocked by a modal child stays open until the child closes.
+  let is_blocked = app
+    .get_window(window_id)
+    .is_some_and(|window| window.children().iter().any(|child| child.modal));
+
+  if is_blocked {
+    return;
+  }
+
+  let is_pending = !app
+    .close_requests
+    .write()
+    .expect("Close requests lock is poisoned")
+    .insert(window_id);
+
+  // The user clicked again while the page is still deciding.
+  if is_pending {
+    return;
+  }
+
+  let is_main = *app
+    .main_window_id
+    .read()
+    .expect("Main window id lock is poisoned")
+    == Some(window_id);
+
+  let (method, windows): (&str, Vec<AppWindow>) = if is_main {
+    (
+      "app.before_quit",
+      app
+        .windows
+        .read()
+        .expect("Failed to acquire lock on windows")
+        .values()
+        .cloned()
+        .collect(),
+    )
+  } else {
+    let windows = app
+      .windows
+      .read()
+      .expect("Failed to acquire lock on windows");
+    let mut ids = descendant_ids(&windows, window_id);
+
+    ids.push(window_id);
+
+    (
+      "window.close_requested",
+      ids
+        .iter()
+        .filter_map(|id| windows.get(id).cloned())
+        .collect(),
+    )
+  };
+
+  tokio::task::spawn(async move {
+    let prevented = is_prevented(method, windows).await;
+
+    app
+      .close_requests
+      .write()
+      .expect("Close requests lock is poisoned")
+      .remove(&window_id);
+
+    if prevented {
+      return;
+    }
+
+    let _ = app
+      .event_loop_proxy
+      .send_event(AppWindowEvent::Close { window_id });
+  });
+}
+
+async fn is_prevented(method: &'static str, windows: Vec<AppWindow>) -> bool {
+  // Pages that never loaded `lenz/ipc` can't answer, so they aren't asked.
+  let requests: Vec<_> = windows
+    .into_iter()
+    .filter(|window| window.is_page_ready())
+    .map(|window| {
+      tokio::task::spawn(async move {
+        window
+          .call_with_timeout(method, vec![], CLOSE_REQUEST_TIMEOUT)
+          .await
+      })
+    })
+    .collect();
+
+  let mut prevented = false;
+
+  for request in requests {
+    match request.await {
+      Ok(Ok(value)) => prevented |= value["prevented"].as_bool().unwrap_or(false),
+      Ok(Err(err)) if err.code == "method_not_found" => {}
+      Ok(Err(err)) => eprintln!("Failed to ask {} > {}", method, err.message),
+      Err(err) => eprintln!("Failed to ask {} > {}", method, err),
+    }
+  }
+
+  prevented
+}
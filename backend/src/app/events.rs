@@ -0,0 +1,76 @@
+use std::sync::{
+  atomic::{AtomicU32, Ordering},
+  Arc,
+};
+
+use super::{middleware::matches_method, App, AppExt};
+
+static NEXT_LISTENER_ID: AtomicU32 = AtomicU32::new(1);
+
+/// An event emitted with `AppExt::emit_to`, as seen by Rust listeners.
+#[derive(Debug, Clone)]
+pub struct AppEvent {
+  pub name: String,
+  pub payload: serde_json::Value,
+  /// Windows the event is also dispatched to.
+  pub target: Vec<u32>,
+}
+
+pub type EventListener = dyn Fn(&AppEvent) + Send + Sync + 'static;
+
+pub struct EventListenerEntry {
+  id: u32,
+  pattern: String,
+  once: bool,
+  listener: Arc<EventListener>,
+}
+
+pub fn add_listener(app: &App, pattern: &str, once: bool, listener: Arc<EventListener>) -> u32 {
+  let id = NEXT_LISTENER_ID.fetch_add(1, Ordering::Relaxed);
+
+  app
+    .event_listeners
+    .write()
+    .expect("Event listeners lock is poisoned")
+    .push(EventListenerEntry {
+      id,
+      pattern: pattern.to_string(),
+      once,
+      listener,
+    });
+
+  id
+}
+
+pub fn remove_listener(app: &App, id: u32) -> bool {
+  let mut listeners = app
+    .event_listeners
+    .write()
+    .expect("Event listeners lock is poisoned");
+  let len = listeners.len();
+
+  listeners.retain(|entry| entry.id != id);
+  listeners.len() != len
+}
+
+/// Calls the listeners matching the event name. Listeners run on the emitting
+/// thread, after the lock is released, so they can emit or subscribe again.
+pub fn notify(app: &App, event: &AppEvent) {
+  let listeners: Vec<(u32, bool, Arc<EventListener>)> = app
+    .event_listeners
+    .read()
+    .expect("Event listeners lock is poisoned")
+    .iter()
+    .filter(|entry| matches_method(&entry.pattern, &event.name))
+    .map(|entry| (entry.id, entry.once, entry.listener.clone()))
+    .collect();
+
+  for (id, once, listener) in listeners {
+    // A `once` listener only runs if this call is the one that removed it.
+    if once && !app.off(id) {
+      continue;
+    }
+
+    listener(event);
+  }
+}
@@ -1,29 +1,347 @@
+use std::{
+  sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+  },
+  time::{Duration, Instant},
+};
+
 use serde::Serialize;
 use serde_json::json;
-use wry::{http::{Method, Request}, RequestAsyncResponder};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use wry::{
+  http::{Method, Request, Response},
+  RequestAsyncResponder,
+};
 
 use crate::app::{App, AppExt};
 
-use super::window::AppWindow;
+use super::{trace::trace_invoke, window::AppWindow};
 
 pub type InvokeHandler = dyn Fn(InvokeCommand, InvokeResponder) + Send + Sync + 'static;
 
+/// Binary arguments are sent out of band and referenced from `args` by a
+/// `{"$blob": <index>}` placeholder.
+pub const INVOKE_BLOB_KEY: &str = "$blob";
+
+/// Largest invoke body accepted, blobs included. Bigger payloads should be
+/// written in chunks.
+pub const MAX_INVOKE_BODY_SIZE: usize = 256 * 1024 * 1024;
+
+/// Identifies an in-flight request by the window that sent it and the
+/// `X-Request-Id` it was given by `ipc.mjs`.
+pub type InvokeRequestKey = (u32, u64);
+
 pub struct InvokeCommand {
   pub app: App,
   pub method: String,
   pub args: Vec<serde_json::Value>,
-  pub window: AppWindow,
+  pub blobs: Vec<Vec<u8>>,
+  /// The window whose page issued the invoke, `None` during a trace replay.
+  pub window: Option<AppWindow>,
+  pub cancellation: CancellationToken,
+}
+
+#[derive(Clone)]
+pub struct CancellationToken {
+  sender: Arc<watch::Sender<bool>>,
+  parent: Option<Box<CancellationToken>>,
+}
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    Self {
+      sender: Arc::new(watch::channel(false).0),
+      parent: None,
+    }
+  }
+
+  /// Token cancelled along with this one, that can also be cancelled without
+  /// affecting it.
+  pub fn child(&self) -> Self {
+    Self {
+      parent: Some(Box::new(self.clone())),
+      ..Self::new()
+    }
+  }
+
+  pub fn cancel(&self) {
+    self.sender.send_replace(true);
+  }
+
+  pub async fn cancelled(&self) {
+    let own = async {
+      let _ = self.sender.subscribe().wait_for(|cancelled| *cancelled).await;
+    };
+
+    match &self.parent {
+      Some(parent) => tokio::select! {
+        _ = own => {}
+        _ = Box::pin(parent.cancelled()) => {}
+      },
+      None => own.await,
+    }
+  }
 }
 
-pub struct InvokeResponder(RequestAsyncResponder);
+impl Default for CancellationToken {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl InvokeCommand {
+  pub fn blob(&self, index: usize) -> Option<&[u8]> {
+    let blob_index = self.args.get(index)?.get(INVOKE_BLOB_KEY)?.as_u64()?;
+
+    self.blobs.get(blob_index as usize).map(Vec::as_slice)
+  }
+}
+
+const INVOKE_STREAM_BUFFER: usize = 8;
+/// Streams the webview stops pulling are dropped after this long, which also
+/// stops their producer.
+const INVOKE_STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const EXPOSE_HEADERS: &str = "X-Invoke-Result,X-Invoke-Stream,X-Invoke-Stream-Length,X-Stream-Done";
+
+static NEXT_STREAM_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Receiving half of a chunked invoke response. The data is pulled by the
+/// webview through `ipc://stream/<id>` until the sender is dropped.
+pub struct InvokeStream {
+  receiver: mpsc::Receiver<Result<Vec<u8>, InvokeError>>,
+  length: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct InvokeStreamSender(mpsc::Sender<Result<Vec<u8>, InvokeError>>);
+
+impl InvokeStream {
+  pub fn channel(length: Option<u64>) -> (InvokeStreamSender, Self) {
+    let (sender, receiver) = mpsc::channel(INVOKE_STREAM_BUFFER);
+
+    (InvokeStreamSender(sender), Self { receiver, length })
+  }
+
+  pub fn length(&self) -> Option<u64> {
+    self.length
+  }
+}
+
+/// A registered stream and the window allowed to pull it.
+pub struct InvokeStreamEntry {
+  window_id: u32,
+  stream: Arc<Mutex<InvokeStream>>,
+  /// When the last pull finished, `None` while one is in progress.
+  idle_since: std::sync::Mutex<Option<Instant>>,
+}
+
+impl InvokeStreamEntry {
+  fn set_pulling(&self, pulling: bool) {
+    *self
+      .idle_since
+      .lock()
+      .expect("Invoke stream lock is poisoned") = (!pulling).then(Instant::now);
+  }
+
+  fn is_idle(&self) -> bool {
+    self
+      .idle_since
+      .lock()
+      .expect("Invoke stream lock is poisoned")
+      .is_some_and(|idle_since| idle_since.elapsed() >= INVOKE_STREAM_IDLE_TIMEOUT)
+  }
+}
+
+/// Makes the stream available to the page of `window_id` and returns the id
+/// it must pull.
+pub fn register_stream(app: &App, window_id: u32, stream: InvokeStream) -> u32 {
+  let id = NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed);
+
+  app
+    .invoke_streams
+    .write()
+    .expect("Invoke streams lock is poisoned")
+    .insert(
+      id,
+      InvokeStreamEntry {
+        window_id,
+        stream: Arc::new(Mutex::new(stream)),
+        idle_since: std::sync::Mutex::new(Some(Instant::now())),
+      },
+    );
+
+  tokio::task::spawn(expire_idle_stream(app.clone(), id));
+
+  id
+}
+
+async fn expire_idle_stream(app: App, id: u32) {
+  loop {
+    tokio::time::sleep(INVOKE_STREAM_IDLE_TIMEOUT).await;
+
+    let mut streams = app
+      .invoke_streams
+      .write()
+      .expect("Invoke streams lock is poisoned");
+
+    match streams.get(&id) {
+      Some(entry) if entry.is_idle() => {
+        streams.remove(&id);
+        return;
+      }
+      Some(_) => {}
+      None => return,
+    }
+  }
+}
+
+/// Drops the streams of a window whose page is gone, stopping their producers.
+pub fn drop_window_streams(app: &App, window_id: u32) {
+  app
+    .invoke_streams
+    .write()
+    .expect("Invoke streams lock is poisoned")
+    .retain(|_, entry| entry.window_id != window_id);
+}
+
+impl InvokeStreamSender {
+  /// Returns `false` when the stream was cancelled or dropped by the reader.
+  pub async fn send(&self, chunk: Vec<u8>) -> bool {
+    self.0.send(Ok(chunk)).await.is_ok()
+  }
+
+  pub async fn fail(self, error: impl Into<InvokeError>) {
+    let _ = self.0.send(Err(error.into())).await;
+  }
+}
+
+/// Sends the HTTP response of an IPC request, either through the webview's
+/// custom protocol or through the development bridge.
+pub struct IpcResponder(Box<dyn FnOnce(Response<Vec<u8>>) + Send + 'static>);
+
+impl IpcResponder {
+  pub fn new<F>(respond: F) -> Self
+  where
+    F: FnOnce(Response<Vec<u8>>) + Send + 'static,
+  {
+    Self(Box::new(respond))
+  }
+
+  pub fn respond<T: Into<std::borrow::Cow<'static, [u8]>>>(self, response: Response<T>) {
+    let (parts, body) = response.into_parts();
+
+    (self.0)(Response::from_parts(parts, body.into().into_owned()))
+  }
+}
+
+impl From<RequestAsyncResponder> for IpcResponder {
+  fn from(responder: RequestAsyncResponder) -> Self {
+    Self::new(move |response| responder.respond(response))
+  }
+}
+
+type InvokeResultMapper = Box<dyn FnOnce(InvokeResult) -> InvokeResult + Send + 'static>;
+
+enum InvokeReplyTarget {
+  Protocol {
+    responder: IpcResponder,
+    window_id: u32,
+  },
+  Channel(oneshot::Sender<InvokeResult>),
+}
+
+pub struct InvokeResponder {
+  app: App,
+  responder: InvokeReplyTarget,
+  request: Option<InvokeRequestKey>,
+  mappers: Vec<InvokeResultMapper>,
+}
 
 impl InvokeResponder {
+  /// Responder for invokes issued by the page of `window_id`, which is also
+  /// the only one allowed to pull a stream it returns.
+  pub fn new(app: App, window_id: u32, responder: IpcResponder) -> Self {
+    Self {
+      app,
+      responder: InvokeReplyTarget::Protocol {
+        responder,
+        window_id,
+      },
+      request: None,
+      mappers: Vec::new(),
+    }
+  }
+
+  /// Responder for invokes issued from Rust. The result is delivered through
+  /// the returned receiver instead of an HTTP response.
+  pub fn channel(app: App) -> (Self, oneshot::Receiver<InvokeResult>) {
+    let (sender, receiver) = oneshot::channel();
+
+    (
+      Self {
+        app,
+        responder: InvokeReplyTarget::Channel(sender),
+        request: None,
+        mappers: Vec::new(),
+      },
+      receiver,
+    )
+  }
+
+  /// Transforms the result before it is sent. Mappers added last run first, so
+  /// an outer middleware sees what the inner ones produced.
+  pub fn map<F>(mut self, mapper: F) -> Self
+  where
+    F: FnOnce(InvokeResult) -> InvokeResult + Send + 'static,
+  {
+    self.mappers.push(Box::new(mapper));
+
+    self
+  }
+
+  pub fn with_request(mut self, request: InvokeRequestKey) -> Self {
+    self.request = Some(request);
+
+    self
+  }
+
   pub fn reply(self, response: InvokeResult) {
-    let Self(responder) = self;
+    let Self {
+      app,
+      responder,
+      request,
+      mappers,
+    } = self;
+
+    let response = mappers
+      .into_iter()
+      .rev()
+      .fold(response, |response, mapper| mapper(response));
+
+    if let Some(request) = request {
+      app
+        .invoke_requests
+        .write()
+        .expect("Invoke requests lock is poisoned")
+        .remove(&request);
+    }
+
+    let (responder, window_id) = match responder {
+      InvokeReplyTarget::Protocol {
+        responder,
+        window_id,
+      } => (responder, window_id),
+      InvokeReplyTarget::Channel(sender) => {
+        let _ = sender.send(response);
+        return;
+      }
+    };
+
     let builder = wry::http::response::Builder::new()
       .header("Access-Control-Allow-Origin", "*")
       .header("Access-Control-Allow-Methods", "POST, OPTIONS")
-      .header("Access-Control-Expose-Headers", "X-Invoke-Result")
+      .header("Access-Control-Expose-Headers", EXPOSE_HEADERS)
       .header(
         "X-Invoke-Result",
         if response.is_ok() { "Ok" } else { "Err" },
@@ -39,6 +357,23 @@ impl InvokeResponder {
           .header("Content-Type", "application/octet-stream")
           .body(value)
           .unwrap(),
+        InvokeResultData::Mixed { value, blobs } => builder
+          .header("Content-Type", "application/x-lenz-invoke")
+          .body(encode_mixed_invoke_body(&value, &blobs))
+          .unwrap(),
+        InvokeResultData::Stream(stream) => {
+          let builder = match stream.length {
+            Some(length) => builder.header("X-Invoke-Stream-Length", length.to_string()),
+            None => builder,
+          };
+          let id = register_stream(&app, window_id, stream);
+
+          builder
+            .header("Content-Type", "application/octet-stream")
+            .header("X-Invoke-Stream", id.to_string())
+            .body(Vec::new())
+            .unwrap()
+        }
       },
       InvokeResult::Err(err) => builder
         .status(200)
@@ -53,12 +388,116 @@ impl InvokeResponder {
 pub enum InvokeResultData {
   Json(serde_json::Value),
   Binary(Vec<u8>),
+  #[serde(skip)]
+  Stream(InvokeStream),
+  /// JSON whose `{"$blob": <index>}` placeholders refer to `blobs`, sent in
+  /// the same framing as mixed invoke bodies.
+  #[serde(skip)]
+  Mixed {
+    value: serde_json::Value,
+    blobs: Vec<Vec<u8>>,
+  },
+}
+
+/// Error returned to the webview. `code` is a stable identifier the frontend
+/// can match on, while `message` is meant for humans.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct InvokeError {
+  pub code: String,
+  pub message: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub data: Option<serde_json::Value>,
+}
+
+impl InvokeError {
+  pub fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+    Self {
+      code: code.into(),
+      message: message.into(),
+      data: None,
+    }
+  }
+
+  pub fn with_data(mut self, data: serde_json::Value) -> Self {
+    self.data = Some(data);
+
+    self
+  }
+
+  pub fn invalid_argument(message: impl Into<String>) -> Self {
+    Self::new("invalid_argument", message)
+  }
+
+  pub fn not_found(message: impl Into<String>) -> Self {
+    Self::new("not_found", message)
+  }
+
+  pub fn cancelled() -> Self {
+    Self::new("cancelled", "Invoke was cancelled")
+  }
+
+  pub fn timeout(method: &str, timeout: std::time::Duration) -> Self {
+    Self::new(
+      "timeout",
+      format!("{} timed out after {} ms", method, timeout.as_millis()),
+    )
+    .with_data(json!({ "method": method, "timeout_ms": timeout.as_millis() as u64 }))
+  }
+
+  pub fn busy(method: &str) -> Self {
+    Self::new("busy", format!("Too many pending invokes for method: {}", method))
+      .with_data(json!({ "method": method }))
+  }
+
+  pub fn method_not_found(method: &str) -> Self {
+    Self::new(
+      "method_not_found",
+      format!("No handler found for method: {}", method),
+    )
+    .with_data(json!({ "method": method }))
+  }
+}
+
+impl std::fmt::Display for InvokeError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}: {}", self.code, self.message)
+  }
+}
+
+impl From<String> for InvokeError {
+  fn from(message: String) -> Self {
+    Self::new("error", message)
+  }
+}
+
+impl From<&str> for InvokeError {
+  fn from(message: &str) -> Self {
+    Self::new("error", message)
+  }
+}
+
+impl From<std::io::Error> for InvokeError {
+  fn from(err: std::io::Error) -> Self {
+    let code = match err.kind() {
+      std::io::ErrorKind::NotFound => "not_found",
+      std::io::ErrorKind::PermissionDenied => "permission_denied",
+      std::io::ErrorKind::AlreadyExists => "already_exists",
+      std::io::ErrorKind::InvalidInput | std::io::ErrorKind::InvalidData => "invalid_argument",
+      std::io::ErrorKind::TimedOut => "timeout",
+      std::io::ErrorKind::Interrupted => "interrupted",
+      std::io::ErrorKind::UnexpectedEof => "unexpected_eof",
+      std::io::ErrorKind::OutOfMemory => "out_of_memory",
+      _ => "io_error",
+    };
+
+    Self::new(code, err.to_string()).with_data(json!({ "kind": format!("{:?}", err.kind()) }))
+  }
 }
 
 #[derive(serde::Serialize)]
 pub enum InvokeResult {
   Ok(InvokeResultData),
-  Err(String),
+  Err(InvokeError),
 }
 
 unsafe impl Send for InvokeResult {}
@@ -76,7 +515,7 @@ impl From<Vec<u8>> for InvokeResult {
   }
 }
 
-impl<E: Into<String>> From<Result<serde_json::Value, E>> for InvokeResult {
+impl<E: Into<InvokeError>> From<Result<serde_json::Value, E>> for InvokeResult {
   fn from(result: Result<serde_json::Value, E>) -> Self {
     match result {
       Ok(value) => Self::Ok(InvokeResultData::Json(value)),
@@ -91,7 +530,7 @@ impl From<&str> for InvokeResult {
   }
 }
 
-impl<E: Into<String>> From<Result<Vec<u8>, E>> for InvokeResult {
+impl<E: Into<InvokeError>> From<Result<Vec<u8>, E>> for InvokeResult {
   fn from(result: Result<Vec<u8>, E>) -> Self {
     match result {
       Ok(value) => Self::Ok(InvokeResultData::Binary(value)),
@@ -100,6 +539,18 @@ impl<E: Into<String>> From<Result<Vec<u8>, E>> for InvokeResult {
   }
 }
 
+impl From<InvokeError> for InvokeResult {
+  fn from(value: InvokeError) -> Self {
+    Self::Err(value)
+  }
+}
+
+impl From<InvokeStream> for InvokeResult {
+  fn from(value: InvokeStream) -> Self {
+    Self::Ok(InvokeResultData::Stream(value))
+  }
+}
+
 impl<T: Serialize> From<Option<T>> for InvokeResult {
   fn from(value: Option<T>) -> Self {
     match value {
@@ -144,98 +595,329 @@ impl InvokeResult {
     Self::Ok(InvokeResultData::Binary(data))
   }
 
-  pub fn error(message: &str) -> Self {
-    Self::Err(message.into())
+  pub fn stream(stream: InvokeStream) -> Self {
+    Self::Ok(InvokeResultData::Stream(stream))
+  }
+
+  pub fn mixed(value: serde_json::Value, blobs: Vec<Vec<u8>>) -> Self {
+    Self::Ok(InvokeResultData::Mixed { value, blobs })
+  }
+
+  pub fn error(error: impl Into<InvokeError>) -> Self {
+    Self::Err(error.into())
   }
 }
 
 pub fn create_ipc_protocol(app: App) -> impl Fn(Request<Vec<u8>>, RequestAsyncResponder) + 'static {
-  move |request, responder| {
-    let app = app.clone();
-
-    match request.method() {
-      &Method::OPTIONS => {
-        responder.respond(
-          wry::http::response::Builder::new()
-          .header("Access-Control-Allow-Origin", "*")
-          .header("Access-Control-Allow-Methods", "POST, OPTIONS")
-          .header("Access-Control-Expose-Headers", "X-Invoke-Result")
-          .header("Access-Control-Allow-Headers", "X-Window-Id,Content-Type")
-          .status(204)
-          .body(Vec::new())
-          .expect("Invalid response")
+  move |request, responder| handle_ipc_request(app.clone(), request, responder.into())
+}
+
+/// Serves an `ipc://` request. The host selects the action: `invoke/<method>`,
+/// `cancel/<request id>` or `stream/<stream id>`.
+pub fn handle_ipc_request(app: App, request: Request<Vec<u8>>, responder: IpcResponder) {
+  match request.method() {
+    &Method::OPTIONS => responder.respond(
+      wry::http::response::Builder::new()
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Access-Control-Allow-Methods", "POST, OPTIONS")
+        .header("Access-Control-Expose-Headers", EXPOSE_HEADERS)
+        .header(
+          "Access-Control-Allow-Headers",
+          "X-Window-Id,X-Request-Id,Content-Type",
         )
-      },
-      &Method::POST => {
-        tokio::task::spawn(async move {
-          let uri = request.uri();
-          let (host, path) = if uri.host().unwrap_or_default() == "localhost" {
-            let (host, path) = uri.path().trim_start_matches("/").split_once("/").unwrap_or_default();
-            (host, path)
-          } else { (uri.host().unwrap_or_default(), uri.path()) };
-    
-          if host != "invoke" {
-            return responder.respond(
-              wry::http::response::Builder::new()
-                .status(400)
-                .body::<Vec<u8>>("Invalid host".into())
-                .unwrap(),
-            );
-          }
-    
-          let method = path.trim_start_matches('/').to_string();
-          let builder = wry::http::response::Builder::new();
-    
-          let window_id: u32 = request
-            .headers()
-            .get("X-Window-Id")
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .parse()
-            .expect("Invalid window id");
-    
-          match serde_json::from_slice::<Vec<serde_json::Value>>(request.body()) {
-            Ok(args) => {
-              app.invoke(
-                InvokeCommand {
-                  app: app.clone(),
-                  method,
-                  args,
-                  window: app.get_window(window_id).unwrap().clone(),
-                },
-                InvokeResponder(responder),
-              );
-            }
-            Err(err) => {
-              responder.respond(
-                builder
-                  .header("Access-Control-Allow-Origin", "*")
-                  .status(400)
-                  .body::<Vec<u8>>(
-                    json!(InvokeResult::Err(err.to_string()))
-                      .to_string()
-                      .into_bytes(),
-                  )
-                  .unwrap(),
-              );
+        .status(204)
+        .body(Vec::new())
+        .expect("Invalid response"),
+    ),
+    &Method::POST => {
+      tokio::task::spawn(async move {
+        let (parts, body) = request.into_parts();
+
+        if body.len() > MAX_INVOKE_BODY_SIZE {
+          return respond_error(
+            responder,
+            413,
+            InvokeError::invalid_argument(format!(
+              "Invoke body is larger than {} bytes",
+              MAX_INVOKE_BODY_SIZE
+            )),
+          );
+        }
+
+        let uri = &parts.uri;
+        let (host, path) = if uri.host().unwrap_or_default() == "localhost" {
+          let (host, path) = uri
+            .path()
+            .trim_start_matches("/")
+            .split_once("/")
+            .unwrap_or_default();
+          (host, path)
+        } else {
+          (uri.host().unwrap_or_default(), uri.path())
+        };
+
+        let window_id: Option<u32> = parts
+          .headers
+          .get("X-Window-Id")
+          .and_then(|value| value.to_str().ok())
+          .and_then(|value| value.parse().ok());
+
+        if host == "stream" {
+          return pull_stream(app, window_id, path, responder).await;
+        }
+
+        if host != "invoke" && host != "cancel" {
+          return responder.respond(
+            wry::http::response::Builder::new()
+              .status(400)
+              .body::<Vec<u8>>("Invalid host".into())
+              .unwrap(),
+          );
+        }
+
+        let method = path.trim_start_matches('/').to_string();
+        let Some(window_id) = window_id else {
+          return respond_error(
+            responder,
+            400,
+            InvokeError::invalid_argument("Missing or invalid X-Window-Id"),
+          );
+        };
+
+        if host == "cancel" {
+          return cancel_request(app, window_id, &method, responder);
+        }
+
+        let request_id = parts
+          .headers
+          .get("X-Request-Id")
+          .and_then(|value| value.to_str().ok())
+          .and_then(|value| value.parse::<u64>().ok());
+
+        let content_type = parts
+          .headers
+          .get("Content-Type")
+          .and_then(|value| value.to_str().ok())
+          .unwrap_or("application/json");
+
+        // The window may have closed while the request was in flight.
+        let Some(window) = app.get_window(window_id) else {
+          return respond_error(
+            responder,
+            404,
+            InvokeError::not_found(format!("Window not found: {}", window_id)),
+          );
+        };
+
+        match parse_invoke_body(content_type, body) {
+          Ok((args, blobs)) => {
+            let cancellation = CancellationToken::new();
+            let mut responder = InvokeResponder::new(app.clone(), window_id, responder);
+
+            if let Some(request_id) = request_id {
+              app
+                .invoke_requests
+                .write()
+                .expect("Invoke requests lock is poisoned")
+                .insert((window_id, request_id), cancellation.clone());
+
+              responder = responder.with_request((window_id, request_id));
             }
-          };
-        });
-      },
-      _ => {
-        responder.respond(
-          wry::http::response::Builder::new()
-          .header("Access-Control-Allow-Origin", "*")
-          .header("Access-Control-Allow-Methods", "POST, OPTIONS")
-          .header("Access-Control-Expose-Headers", "X-Invoke-Result")
-          .status(405)
-          .body("Method not Allowed!".as_bytes())
-          .expect("Invalid response")
-        )
-      }
-    };
+
+            let command = InvokeCommand {
+              app: app.clone(),
+              method,
+              args,
+              blobs,
+              window: Some(window),
+              cancellation,
+            };
+            let responder = trace_invoke(&app, &command, request_id, responder);
+
+            app.invoke(command, responder);
+          }
+          Err(err) => respond_error(responder, 400, InvokeError::invalid_argument(err)),
+        };
+      });
+    }
+    _ => responder.respond(
+      wry::http::response::Builder::new()
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Access-Control-Allow-Methods", "POST, OPTIONS")
+        .header("Access-Control-Expose-Headers", EXPOSE_HEADERS)
+        .status(405)
+        .body("Method not Allowed!".as_bytes())
+        .expect("Invalid response"),
+    ),
+  }
+}
+
+/// Replies to a request that never reached a handler, in the same shape as an
+/// invoke error.
+fn respond_error(responder: IpcResponder, status: u16, error: InvokeError) {
+  responder.respond(
+    wry::http::response::Builder::new()
+      .header("Access-Control-Allow-Origin", "*")
+      .header("Access-Control-Expose-Headers", EXPOSE_HEADERS)
+      .header("X-Invoke-Result", "Err")
+      .header("Content-Type", "application/json")
+      .status(status)
+      .body::<Vec<u8>>(json!(error).to_string().into_bytes())
+      .unwrap(),
+  );
+}
+
+fn cancel_request(app: App, window_id: u32, request_id: &str, responder: IpcResponder) {
+  let cancellation = request_id.parse::<u64>().ok().and_then(|request_id| {
+    app
+      .invoke_requests
+      .write()
+      .expect("Invoke requests lock is poisoned")
+      .remove(&(window_id, request_id))
+  });
+
+  if let Some(cancellation) = cancellation {
+    cancellation.cancel();
+  }
+
+  responder.respond(
+    wry::http::response::Builder::new()
+      .header("Access-Control-Allow-Origin", "*")
+      .status(204)
+      .body(Vec::new())
+      .unwrap(),
+  );
+}
+
+type InvokeBody = (Vec<serde_json::Value>, Vec<Vec<u8>>);
+
+fn parse_invoke_body(content_type: &str, body: Vec<u8>) -> Result<InvokeBody, String> {
+  match content_type.split(';').next().unwrap_or_default().trim() {
+    "application/octet-stream" => Ok((vec![json!({ INVOKE_BLOB_KEY: 0 })], vec![body])),
+    "application/x-lenz-invoke" => parse_mixed_invoke_body(&body),
+    _ => serde_json::from_slice(&body)
+      .map(|args| (args, Vec::new()))
+      .map_err(|err| err.to_string()),
+  }
+}
+
+/// Mixed bodies are a sequence of frames, each prefixed by its length as a
+/// little-endian `u32`. The first frame holds the JSON arguments and the
+/// following ones the blobs they reference.
+fn parse_mixed_invoke_body(body: &[u8]) -> Result<InvokeBody, String> {
+  let mut offset = 0;
+  let mut frames = Vec::new();
+
+  while offset < body.len() {
+    let length = body
+      .get(offset..offset + 4)
+      .map(|header| u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize)
+      .ok_or("Truncated invoke body")?;
+    let end = (offset + 4)
+      .checked_add(length)
+      .ok_or("Truncated invoke body")?;
+    let frame = body.get(offset + 4..end).ok_or("Truncated invoke body")?;
+
+    frames.push(frame);
+    offset = end;
   }
+
+  let (args, blobs) = frames.split_first().ok_or("Empty invoke body")?;
+  let args = serde_json::from_slice(args).map_err(|err| err.to_string())?;
+
+  Ok((args, blobs.iter().map(|blob| blob.to_vec()).collect()))
+}
+
+/// Encodes a value and its blobs in the framing read by
+/// `parse_mixed_invoke_body`.
+fn encode_mixed_invoke_body(value: &serde_json::Value, blobs: &[Vec<u8>]) -> Vec<u8> {
+  let value = value.to_string().into_bytes();
+  let frames = std::iter::once(&value).chain(blobs);
+  let mut body = Vec::with_capacity(frames.clone().map(|frame| 4 + frame.len()).sum());
+
+  for frame in frames {
+    body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+    body.extend_from_slice(frame);
+  }
+
+  body
+}
+
+/// Streams can only be pulled or cancelled by the window they were returned
+/// to; other windows get the same 404 as for an unknown id.
+async fn pull_stream(app: App, window_id: Option<u32>, path: &str, responder: IpcResponder) {
+  let builder = wry::http::response::Builder::new()
+    .header("Access-Control-Allow-Origin", "*")
+    .header("Access-Control-Expose-Headers", EXPOSE_HEADERS);
+  let path = path.trim_start_matches('/');
+  let (id, action) = path.split_once('/').unwrap_or((path, ""));
+
+  let stream = id.parse::<u32>().ok().and_then(|id| {
+    app
+      .invoke_streams
+      .read()
+      .expect("Invoke streams lock is poisoned")
+      .get(&id)
+      .filter(|entry| Some(entry.window_id) == window_id)
+      .map(|entry| {
+        entry.set_pulling(true);
+        (id, entry.stream.clone())
+      })
+  });
+
+  let (id, stream) = match stream {
+    Some(stream) => stream,
+    None => {
+      return responder.respond(
+        builder
+          .status(404)
+          .body::<Vec<u8>>("Stream not found".into())
+          .unwrap(),
+      )
+    }
+  };
+
+  if action == "cancel" {
+    app
+      .invoke_streams
+      .write()
+      .expect("Invoke streams lock is poisoned")
+      .remove(&id);
+
+    return responder.respond(builder.status(204).body(Vec::new()).unwrap());
+  }
+
+  let chunk = stream.lock().await.receiver.recv().await;
+  let mut streams = app
+    .invoke_streams
+    .write()
+    .expect("Invoke streams lock is poisoned");
+
+  if matches!(chunk, Some(Ok(_))) {
+    if let Some(entry) = streams.get(&id) {
+      entry.set_pulling(false);
+    }
+  } else {
+    streams.remove(&id);
+  }
+
+  drop(streams);
+
+  responder.respond(match chunk {
+    Some(Ok(chunk)) => builder
+      .header("Content-Type", "application/octet-stream")
+      .body(chunk)
+      .unwrap(),
+    Some(Err(err)) => builder
+      .status(500)
+      .header("Content-Type", "application/json")
+      .body(json!(err).to_string().into_bytes())
+      .unwrap(),
+    None => builder
+      .header("X-Stream-Done", "true")
+      .body(Vec::new())
+      .unwrap(),
+  })
 }
 
 #[macro_export]
@@ -254,25 +936,169 @@ macro_rules! async_handler {
 
 #[macro_export]
 macro_rules! async_invoke_handlers {
-  ($app:expr, {$($name:expr => $handler:expr),*}) => {
+  ($app:expr, {$($name:expr => $handler:path $(: $returns:ty)?),*}) => {
     $(
       $app.add_invoke_handler($name, |command, responder| {
         tokio::task::spawn(async move {
-          let result = $handler(command).await;
-          responder.reply(result.into());
+          let cancellation = command.cancellation.clone();
+          let result = match $crate::app::invoke_args::InvokeHandlerFn::call(&$handler, command) {
+            Ok(future) => tokio::select! {
+              result = future => result.into(),
+              _ = cancellation.cancelled() => $crate::app::invoke::InvokeError::cancelled().into(),
+            },
+            Err(err) => err,
+          };
+          responder.reply(result);
         });
       });
+      $app.describe_invoke_handler($name, $crate::app::schema::InvokeDescription {
+        args: $crate::app::invoke_args::InvokeHandlerFn::args_schema(&$handler),
+        returns: $crate::invoke_returns_schema!($($returns)?),
+      });
     )*
   };
 }
 
 #[macro_export]
 macro_rules! blocking_invoke_handlers {
-  ($app:expr, {$($name:expr => $handler:expr),*}) => {
+  ($app:expr, {$($name:expr => $handler:path $(: $returns:ty)?),*}) => {
     $(
       $app.add_invoke_handler($name, |command, responder| {
-        responder.reply($handler(command).into());
+        let result = match $crate::app::invoke_args::InvokeHandlerFn::call(&$handler, command) {
+          Ok(result) => result.into(),
+          Err(err) => err,
+        };
+        responder.reply(result);
+      });
+      $app.describe_invoke_handler($name, $crate::app::schema::InvokeDescription {
+        args: $crate::app::invoke_args::InvokeHandlerFn::args_schema(&$handler),
+        returns: $crate::invoke_returns_schema!($($returns)?),
       });
     )*
   };
 }
+
+/// Return schema of a handler registered with an optional `: Type` annotation.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! invoke_returns_schema {
+  () => {
+    $crate::app::schema::Schema::Any
+  };
+  ($returns:ty) => {
+    $crate::app::schema::schema_of::<$returns>()
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn frame(data: &[u8]) -> Vec<u8> {
+    let mut frame = (data.len() as u32).to_le_bytes().to_vec();
+    frame.extend_from_slice(data);
+    frame
+  }
+
+  #[test]
+  fn parses_mixed_body_with_blob_placeholders() {
+    let body = [
+      frame(br#"["name", {"$blob": 0}, {"$blob": 1}]"#),
+      frame(b"first"),
+      frame(b""),
+    ]
+    .concat();
+
+    let (args, blobs) = parse_mixed_invoke_body(&body).unwrap();
+
+    assert_eq!(
+      args,
+      vec![json!("name"), json!({ "$blob": 0 }), json!({ "$blob": 1 })]
+    );
+    assert_eq!(blobs, vec![b"first".to_vec(), Vec::new()]);
+  }
+
+  #[test]
+  fn parses_mixed_body_without_blobs() {
+    let (args, blobs) = parse_mixed_invoke_body(&frame(b"[1, 2]")).unwrap();
+
+    assert_eq!(args, vec![json!(1), json!(2)]);
+    assert!(blobs.is_empty());
+  }
+
+  #[test]
+  fn encoded_mixed_body_round_trips() {
+    let value = json!([{ "ok": true, "value": { "$blob": 0 } }]);
+    let blobs = vec![b"bytes".to_vec()];
+
+    let (args, decoded) =
+      parse_mixed_invoke_body(&encode_mixed_invoke_body(&value, &blobs)).unwrap();
+
+    assert_eq!(json!(args), value);
+    assert_eq!(decoded, blobs);
+  }
+
+  #[test]
+  fn rejects_empty_mixed_body() {
+    assert_eq!(
+      parse_mixed_invoke_body(&[]).unwrap_err(),
+      "Empty invoke body"
+    );
+  }
+
+  #[test]
+  fn rejects_truncated_frame_header() {
+    let mut body = frame(b"[]");
+    body.extend_from_slice(&[1, 0]);
+
+    assert_eq!(
+      parse_mixed_invoke_body(&body).unwrap_err(),
+      "Truncated invoke body"
+    );
+  }
+
+  #[test]
+  fn rejects_frame_longer_than_body() {
+    let mut body = frame(b"[]");
+    body.extend_from_slice(&16u32.to_le_bytes());
+    body.extend_from_slice(b"short");
+
+    assert_eq!(
+      parse_mixed_invoke_body(&body).unwrap_err(),
+      "Truncated invoke body"
+    );
+  }
+
+  #[test]
+  fn rejects_overflowing_frame_length() {
+    let mut body = frame(b"[]");
+    body.extend_from_slice(&u32::MAX.to_le_bytes());
+
+    assert_eq!(
+      parse_mixed_invoke_body(&body).unwrap_err(),
+      "Truncated invoke body"
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_json_arguments() {
+    assert!(parse_mixed_invoke_body(&frame(b"{")).is_err());
+  }
+
+  #[test]
+  fn wraps_octet_stream_body_in_a_blob_placeholder() {
+    let (args, blobs) = parse_invoke_body("application/octet-stream", b"data".to_vec()).unwrap();
+
+    assert_eq!(args, vec![json!({ INVOKE_BLOB_KEY: 0 })]);
+    assert_eq!(blobs, vec![b"data".to_vec()]);
+  }
+
+  #[test]
+  fn parses_json_body_by_default() {
+    let (args, blobs) =
+      parse_invoke_body("application/json; charset=utf-8", b"[true]".to_vec()).unwrap();
+
+    assert_eq!(args, vec![json!(true)]);
+    assert!(blobs.is_empty());
+  }
+}
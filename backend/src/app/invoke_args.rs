@@ -0,0 +1,143 @@
+use serde::de::DeserializeOwned;
+
+use super::{
+  invoke::{CancellationToken, InvokeCommand, InvokeError, InvokeResult},
+  schema::{schema_of, Schema},
+  window::AppWindow,
+  App,
+};
+
+/// Error code of invokes that need a calling window but were issued without
+/// one, like during a trace replay.
+pub const NO_WINDOW_CODE: &str = "no_window";
+
+/// Values taken from the invoke context instead of the positional arguments.
+/// Only the first parameter of a handler can be a context value.
+pub trait FromInvokeCommand: Sized {
+  fn from_invoke_command(command: &InvokeCommand) -> Result<Self, InvokeResult>;
+}
+
+impl FromInvokeCommand for App {
+  fn from_invoke_command(command: &InvokeCommand) -> Result<Self, InvokeResult> {
+    Ok(command.app.clone())
+  }
+}
+
+impl FromInvokeCommand for AppWindow {
+  fn from_invoke_command(command: &InvokeCommand) -> Result<Self, InvokeResult> {
+    command.window.clone().ok_or_else(|| {
+      InvokeError::new(
+        NO_WINDOW_CODE,
+        format!("Method {} needs a calling window", command.method),
+      )
+      .into()
+    })
+  }
+}
+
+impl FromInvokeCommand for CancellationToken {
+  fn from_invoke_command(command: &InvokeCommand) -> Result<Self, InvokeResult> {
+    Ok(command.cancellation.clone())
+  }
+}
+
+pub fn invoke_arg<T: DeserializeOwned>(
+  command: &InvokeCommand,
+  index: usize,
+) -> Result<T, InvokeResult> {
+  let value = command
+    .args
+    .get(index)
+    .cloned()
+    .unwrap_or(serde_json::Value::Null);
+  let is_missing = value.is_null();
+
+  serde_json::from_value(value).map_err(|err| {
+    if is_missing {
+      InvokeError::invalid_argument(format!("Argument {} is required", index))
+    } else {
+      InvokeError::invalid_argument(format!("Invalid argument {}: {}", index, err))
+    }
+    .with_data(serde_json::json!({ "index": index }))
+    .into()
+  })
+}
+
+pub enum WithContext {}
+
+/// Adapts a handler function to an `InvokeCommand`, deserializing each
+/// parameter from the positional argument of the same index. Handlers taking
+/// the raw `InvokeCommand` keep working unchanged.
+pub trait InvokeHandlerFn<Args> {
+  type Output;
+
+  fn call(&self, command: InvokeCommand) -> Result<Self::Output, InvokeResult>;
+
+  /// Schemas of the positional arguments, `None` when they are not known.
+  fn args_schema(&self) -> Option<Vec<Schema>>;
+}
+
+impl<F, R> InvokeHandlerFn<(InvokeCommand,)> for F
+where
+  F: Fn(InvokeCommand) -> R,
+{
+  type Output = R;
+
+  fn call(&self, command: InvokeCommand) -> Result<R, InvokeResult> {
+    Ok(self(command))
+  }
+
+  fn args_schema(&self) -> Option<Vec<Schema>> {
+    None
+  }
+}
+
+macro_rules! impl_invoke_handler_fn {
+  ($($arg:ident => $index:tt),*) => {
+    impl<F, R, $($arg),*> InvokeHandlerFn<($($arg,)*)> for F
+    where
+      F: Fn($($arg),*) -> R,
+      $($arg: DeserializeOwned,)*
+    {
+      type Output = R;
+
+      #[allow(unused_variables)]
+      fn call(&self, command: InvokeCommand) -> Result<R, InvokeResult> {
+        Ok(self($(invoke_arg::<$arg>(&command, $index)?),*))
+      }
+
+      fn args_schema(&self) -> Option<Vec<Schema>> {
+        Some(vec![$(schema_of::<$arg>()),*])
+      }
+    }
+
+    impl<F, R, C, $($arg),*> InvokeHandlerFn<(WithContext, C, $($arg,)*)> for F
+    where
+      F: Fn(C, $($arg),*) -> R,
+      C: FromInvokeCommand,
+      $($arg: DeserializeOwned,)*
+    {
+      type Output = R;
+
+      fn call(&self, command: InvokeCommand) -> Result<R, InvokeResult> {
+        Ok(self(
+          C::from_invoke_command(&command)?,
+          $(invoke_arg::<$arg>(&command, $index)?),*
+        ))
+      }
+
+      fn args_schema(&self) -> Option<Vec<Schema>> {
+        Some(vec![$(schema_of::<$arg>()),*])
+      }
+    }
+  };
+}
+
+impl_invoke_handler_fn!();
+impl_invoke_handler_fn!(A0 => 0);
+impl_invoke_handler_fn!(A0 => 0, A1 => 1);
+impl_invoke_handler_fn!(A0 => 0, A1 => 1, A2 => 2);
+impl_invoke_handler_fn!(A0 => 0, A1 => 1, A2 => 2, A3 => 3);
+impl_invoke_handler_fn!(A0 => 0, A1 => 1, A2 => 2, A3 => 3, A4 => 4);
+impl_invoke_handler_fn!(A0 => 0, A1 => 1, A2 => 2, A3 => 3, A4 => 4, A5 => 5);
+impl_invoke_handler_fn!(A0 => 0, A1 => 1, A2 => 2, A3 => 3, A4 => 4, A5 => 5, A6 => 6);
@@ -0,0 +1,275 @@
+use std::{
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{
+  invoke::{InvokeCommand, InvokeError, InvokeResponder, InvokeResult},
+  middleware::{matches_method, InvokeNext},
+  App,
+};
+
+/// Limits applied to every method matching a pattern. Invokes over
+/// `max_concurrent` wait in a queue of at most `max_queued` entries; the
+/// timeout also counts the time spent in the queue.
+///
+/// When several patterns match a method, the limits of all of them apply.
+#[derive(Debug, Clone, Default)]
+pub struct InvokeLimits {
+  timeout: Option<Duration>,
+  max_concurrent: Option<usize>,
+  max_queued: Option<usize>,
+}
+
+impl InvokeLimits {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = Some(timeout);
+    self
+  }
+
+  pub fn with_max_concurrent(mut self, max_concurrent: usize) -> Self {
+    self.max_concurrent = Some(max_concurrent.max(1));
+    self
+  }
+
+  pub fn with_max_queued(mut self, max_queued: usize) -> Self {
+    self.max_queued = Some(max_queued);
+    self
+  }
+}
+
+/// State shared by every invoke of a pattern, so the concurrency limit is
+/// counted across all the methods it matches.
+pub struct InvokeLimiter {
+  pattern: String,
+  limits: InvokeLimits,
+  permits: Option<Arc<Semaphore>>,
+  queued: AtomicUsize,
+}
+
+impl InvokeLimiter {
+  pub fn new(pattern: &str, limits: InvokeLimits) -> Self {
+    Self {
+      pattern: pattern.to_string(),
+      permits: limits
+        .max_concurrent
+        .map(|max| Arc::new(Semaphore::new(max))),
+      limits,
+      queued: AtomicUsize::new(0),
+    }
+  }
+
+  /// Every limiter matching `method`, in the order they are applied.
+  pub fn find(app: &App, method: &str) -> Vec<Arc<Self>> {
+    Self::matching(
+      &app
+        .invoke_limits
+        .read()
+        .expect("Invoke limits lock is poisoned"),
+      method,
+    )
+  }
+
+  /// Broader patterns come first, so their limits wrap the ones of more
+  /// specific patterns and exact names.
+  fn matching(limiters: &[Arc<Self>], method: &str) -> Vec<Arc<Self>> {
+    let mut matching: Vec<Arc<Self>> = limiters
+      .iter()
+      .filter(|limiter| matches_method(&limiter.pattern, method))
+      .cloned()
+      .collect();
+
+    matching.sort_by_key(|limiter| (!limiter.pattern.ends_with('*'), limiter.pattern.len()));
+    matching
+  }
+
+  pub fn pattern(&self) -> &str {
+    &self.pattern
+  }
+
+  /// Runs `next` within the limits. Busy and timeout errors are replied
+  /// through `responder`, so the middlewares that ran before see them.
+  pub fn dispatch(
+    self: Arc<Self>,
+    mut command: InvokeCommand,
+    responder: InvokeResponder,
+    next: InvokeNext,
+  ) {
+    let method = command.method.clone();
+
+    let Some(admission) = self.admit() else {
+      responder.reply(InvokeError::busy(&method).into());
+      return;
+    };
+
+    // A timeout only cancels this invoke, not the others sharing its token,
+    // like the calls of a batch.
+    let cancellation = command.cancellation.child();
+    command.cancellation = cancellation.clone();
+
+    let run = async move {
+      let _permit = match admission {
+        Admission::Unlimited => None,
+        Admission::Running(permit) => Some(permit),
+        Admission::Queued { permits, slot } => {
+          let permit = tokio::select! {
+            permit = permits.acquire_owned() => permit.ok(),
+            _ = command.cancellation.cancelled() => None,
+          };
+
+          drop(slot);
+
+          match permit {
+            Some(permit) => Some(permit),
+            None => return InvokeError::cancelled().into(),
+          }
+        }
+      };
+
+      let (inner, receiver) = InvokeResponder::channel(command.app.clone());
+
+      next.run(command, inner);
+
+      receiver
+        .await
+        .unwrap_or_else(|_| InvokeError::new("internal", "Handler dropped the request").into())
+    };
+
+    tokio::task::spawn(async move {
+      let result: InvokeResult = match self.limits.timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, run).await {
+          Ok(result) => result,
+          Err(_) => {
+            cancellation.cancel();
+            InvokeError::timeout(&method, timeout).into()
+          }
+        },
+        None => run.await,
+      };
+
+      responder.reply(result);
+    });
+  }
+
+  /// Takes a permit, or reserves a place in the queue when none is left.
+  /// Returns `None` when the queue is full.
+  fn admit(self: &Arc<Self>) -> Option<Admission> {
+    let Some(permits) = &self.permits else {
+      return Some(Admission::Unlimited);
+    };
+
+    if let Ok(permit) = permits.clone().try_acquire_owned() {
+      return Some(Admission::Running(permit));
+    }
+
+    let max_queued = self.limits.max_queued.unwrap_or(usize::MAX);
+
+    self
+      .queued
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+        (queued < max_queued).then_some(queued + 1)
+      })
+      .ok()?;
+
+    Some(Admission::Queued {
+      permits: permits.clone(),
+      slot: QueueSlot(self.clone()),
+    })
+  }
+}
+
+enum Admission {
+  Unlimited,
+  Running(OwnedSemaphorePermit),
+  Queued {
+    permits: Arc<Semaphore>,
+    slot: QueueSlot,
+  },
+}
+
+/// Place reserved in the queue of a limiter, released when dropped, whether
+/// the invoke got a permit, was cancelled or timed out.
+struct QueueSlot(Arc<InvokeLimiter>);
+
+impl Drop for QueueSlot {
+  fn drop(&mut self) {
+    self.0.queued.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn limiters(patterns: &[&str]) -> Vec<Arc<InvokeLimiter>> {
+    patterns
+      .iter()
+      .map(|pattern| Arc::new(InvokeLimiter::new(pattern, InvokeLimits::new())))
+      .collect()
+  }
+
+  fn matching(limiters: &[Arc<InvokeLimiter>], method: &str) -> Vec<String> {
+    InvokeLimiter::matching(limiters, method)
+      .iter()
+      .map(|limiter| limiter.pattern().to_string())
+      .collect()
+  }
+
+  #[test]
+  fn applies_every_matching_limiter_from_broadest() {
+    let limiters = limiters(&["fs.read", "*", "fs.*", "window.*"]);
+
+    assert_eq!(matching(&limiters, "fs.read"), ["*", "fs.*", "fs.read"]);
+    assert_eq!(matching(&limiters, "fs.write"), ["*", "fs.*"]);
+    assert_eq!(matching(&limiters, "dialog.show"), ["*"]);
+  }
+
+  #[test]
+  fn orders_longer_prefixes_inside_shorter_ones() {
+    let limiters = limiters(&["fs.watch.*", "fs.*"]);
+
+    assert_eq!(
+      matching(&limiters, "fs.watch.close"),
+      ["fs.*", "fs.watch.*"]
+    );
+  }
+
+  #[test]
+  fn finds_nothing_without_a_matching_pattern() {
+    let limiters = limiters(&["fs.*", "window.close"]);
+
+    assert!(matching(&limiters, "window.create").is_empty());
+    assert!(matching(&[], "fs.read").is_empty());
+  }
+
+  #[test]
+  fn reserves_queue_slots_until_released() {
+    let limiter = Arc::new(InvokeLimiter::new(
+      "fs.*",
+      InvokeLimits::new()
+        .with_max_concurrent(1)
+        .with_max_queued(1),
+    ));
+
+    let running = limiter.admit();
+    let queued = limiter.admit();
+
+    assert!(matches!(running, Some(Admission::Running(_))));
+    assert!(matches!(queued, Some(Admission::Queued { .. })));
+    assert!(limiter.admit().is_none());
+
+    drop(queued);
+
+    assert!(matches!(limiter.admit(), Some(Admission::Queued { .. })));
+    assert_eq!(limiter.queued.load(Ordering::SeqCst), 0);
+  }
+}
@@ -0,0 +1,614 @@
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  sync::atomic::{AtomicU32, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::oneshot;
+
+use super::{
+  window::{AppWindow, AppWindowEvent, AppWindowExt},
+  App, AppExt,
+};
+
+/// Menu bar shown by windows built `with_menu_bar()`.
+pub const DEFAULT_MENU_BAR: &str = "main";
+
+const COMMAND_ITEM_PREFIX: &str = "command";
+const POPUP_ITEM_PREFIX: &str = "popup";
+
+/// Target of the menu bar items on macOS, where a single menu bar serves the
+/// whole app and its commands go to the focused window.
+#[cfg(target_os = "macos")]
+const FOCUSED_WINDOW: &str = "focused";
+
+static NEXT_POPUP_ID: AtomicU32 = AtomicU32::new(1);
+
+thread_local! {
+  /// Native menu bars by window id. muda menus can't leave the event loop
+  /// thread, so they are kept here instead of in the windows.
+  static MENU_BARS: RefCell<HashMap<u32, MenuBar>> = RefCell::default();
+  /// The context menu open in each window, for the same reason.
+  static POPUPS: RefCell<HashMap<u32, Popup>> = RefCell::default();
+}
+
+/// A native menu bar and the id of the model it shows.
+struct MenuBar {
+  id: String,
+  menu: muda::Menu,
+  #[cfg(target_os = "windows")]
+  hwnd: isize,
+}
+
+/// A top-level menu of a menu bar. Extensions place their commands in it by
+/// its `id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Menu {
+  pub id: String,
+  pub label: String,
+  #[serde(default)]
+  pub items: Vec<MenuItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MenuItem {
+  /// Emits `command.execute` to the window when selected. Without a label,
+  /// the title the command is contributed with is shown.
+  Command {
+    command: String,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    accelerator: Option<String>,
+  },
+  Submenu {
+    label: String,
+    #[serde(default)]
+    items: Vec<MenuItem>,
+  },
+  Separator,
+}
+
+impl Menu {
+  pub fn new(id: impl Into<String>, label: impl Into<String>, items: Vec<MenuItem>) -> Self {
+    Self {
+      id: id.into(),
+      label: label.into(),
+      items,
+    }
+  }
+}
+
+impl MenuItem {
+  pub fn command(command: &str, label: &str, accelerator: Option<&str>) -> Self {
+    Self::Command {
+      command: command.to_string(),
+      label: Some(label.to_string()),
+      accelerator: accelerator.map(str::to_string),
+    }
+  }
+}
+
+/// An item of a context menu shown with `menu.popup`. Selecting an `item` or a
+/// `checkbox` resolves the popup with its `id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PopupMenuItem {
+  Item {
+    id: String,
+    label: String,
+    #[serde(default)]
+    disabled: bool,
+    #[serde(default)]
+    accelerator: Option<String>,
+  },
+  Checkbox {
+    id: String,
+    label: String,
+    #[serde(default)]
+    checked: bool,
+    #[serde(default)]
+    disabled: bool,
+  },
+  Submenu {
+    label: String,
+    #[serde(default)]
+    items: Vec<PopupMenuItem>,
+    #[serde(default)]
+    disabled: bool,
+  },
+  Separator,
+}
+
+/// Position of a context menu in CSS pixels from the top-left corner of the
+/// page, like the `clientX` and `clientY` of a mouse event.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PopupPosition {
+  pub x: f64,
+  pub y: f64,
+}
+
+/// The context menu open in a window, kept alive until an item is selected or
+/// it is dismissed.
+struct Popup {
+  id: u32,
+  // Never read, but GTK menus are shown asynchronously and go away with it.
+  #[allow(dead_code)]
+  menu: muda::Menu,
+  reply: oneshot::Sender<Option<String>>,
+}
+
+/// Rebuilds the menu bars of every window on the event loop thread, after the
+/// model or the active extensions changed.
+pub fn refresh(app: &App) {
+  let _ = app.event_loop_proxy.send_event(AppWindowEvent::UpdateMenus);
+}
+
+/// The menus of menu bar `id`, with the commands placed by active extensions
+/// appended to the menus they name, after a separator.
+pub fn resolve(app: &App, id: &str) -> Vec<Menu> {
+  let mut menus = app
+    .menu_bars
+    .read()
+    .expect("Menu bars lock is poisoned")
+    .get(id)
+    .cloned()
+    .unwrap_or_default();
+  let state = app.state.read().expect("State lock is poisoned");
+
+  for extension in state.extension_host.extensions() {
+    let mut separated = Vec::new();
+
+    for contribution in &extension.manifest().contributes.menus {
+      let index = match menus.iter().position(|menu| menu.id == contribution.menu) {
+        Some(index) => index,
+        None => {
+          menus.push(Menu::new(
+            &contribution.menu,
+            &contribution.menu,
+            Vec::new(),
+          ));
+          menus.len() - 1
+        }
+      };
+      let menu = &mut menus[index];
+
+      if !separated.contains(&index) {
+        if !menu.items.is_empty() {
+          menu.items.push(MenuItem::Separator);
+        }
+
+        separated.push(index);
+      }
+
+      menu.items.push(MenuItem::Command {
+        command: contribution.command.clone(),
+        label: None,
+        accelerator: contribution.accelerator.clone(),
+      });
+    }
+  }
+
+  menus
+}
+
+/// The active extension contributing `command`, and the title it gives it.
+fn command_owner(app: &App, command: &str) -> Option<(String, Option<String>)> {
+  let state = app.state.read().expect("State lock is poisoned");
+  let owner = state.extension_host.extensions().find_map(|extension| {
+    extension
+      .manifest()
+      .contributes
+      .commands
+      .iter()
+      .find(|contributed| contributed.id == command)
+      .map(|contributed| (extension.id(), contributed.title.clone()))
+  });
+
+  owner
+}
+
+/// Adds the native menu bar of a window built `with_menu_bar`. On macOS the
+/// first one becomes the menu bar of the app, which the other windows share.
+/// Must be called from the event loop thread.
+pub fn install(window: &AppWindow) {
+  let Some(id) = &window.menu_bar else {
+    return;
+  };
+
+  #[cfg(target_os = "macos")]
+  if MENU_BARS.with_borrow(|bars| !bars.is_empty()) {
+    return;
+  }
+
+  let menu = muda::Menu::new();
+
+  #[cfg(target_os = "windows")]
+  let result = {
+    use tao::platform::windows::WindowExtWindows;
+    unsafe { menu.init_for_hwnd(window.tao_window.hwnd()) }
+  };
+
+  #[cfg(target_os = "macos")]
+  let result = {
+    menu.init_for_nsapp();
+    Ok::<(), muda::Error>(())
+  };
+
+  #[cfg(any(target_os = "ios", target_os = "android"))]
+  let result = Ok::<(), muda::Error>(());
+
+  #[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "android"
+  )))]
+  let result = {
+    use tao::platform::unix::WindowExtUnix;
+    menu.init_for_gtk_window(
+      window.tao_window.gtk_window(),
+      window.tao_window.default_vbox(),
+    )
+  };
+
+  if let Err(err) = result {
+    eprintln!("Failed to add menu bar to window {} > {}", window.id(), err);
+  }
+
+  let bar = MenuBar {
+    id: id.clone(),
+    menu,
+    #[cfg(target_os = "windows")]
+    hwnd: {
+      use tao::platform::windows::WindowExtWindows;
+      window.tao_window.hwnd()
+    },
+  };
+
+  rebuild(&window.app, window.id(), &bar);
+  MENU_BARS.with_borrow_mut(|bars| bars.insert(window.id(), bar));
+}
+
+/// Drops the menu bar of a closed window, and dismisses its context menu. The
+/// app menu bar of macOS stays. Must be called from the event loop thread.
+pub fn uninstall(window_id: u32) {
+  POPUPS.with_borrow_mut(|popups| popups.remove(&window_id));
+
+  #[cfg(not(target_os = "macos"))]
+  MENU_BARS.with_borrow_mut(|bars| bars.remove(&window_id));
+
+  #[cfg(target_os = "macos")]
+  let _ = window_id;
+}
+
+/// Replaces the items of every menu bar with the current model. Must be called
+/// from the event loop thread.
+pub fn update(app: &App) {
+  MENU_BARS.with_borrow(|bars| {
+    for (window_id, bar) in bars {
+      rebuild(app, *window_id, bar);
+    }
+  });
+}
+
+fn rebuild(app: &App, window_id: u32, bar: &MenuBar) {
+  #[cfg(target_os = "macos")]
+  let target = {
+    let _ = window_id;
+    FOCUSED_WINDOW.to_string()
+  };
+
+  #[cfg(not(target_os = "macos"))]
+  let target = window_id.to_string();
+
+  while bar.menu.remove_at(0).is_some() {}
+
+  for model in resolve(app, &bar.id) {
+    let submenu = muda::Submenu::new(&model.label, true);
+
+    append_items(app, &target, &submenu, &model.items);
+
+    if let Err(err) = bar.menu.append(&submenu) {
+      eprintln!("Failed to add menu {} > {}", model.id, err);
+    }
+  }
+}
+
+/// Hands menu bar accelerators to Windows, which only triggers them through
+/// `TranslateAcceleratorW`. Installed with `EventLoopBuilderExtWindows::with_msg_hook`,
+/// so it runs on the event loop thread.
+#[cfg(target_os = "windows")]
+pub fn translate_accelerator(msg: *const std::ffi::c_void) -> bool {
+  use windows_sys::Win32::UI::WindowsAndMessaging::{
+    GetAncestor, TranslateAcceleratorW, GA_ROOT, MSG,
+  };
+
+  let msg = msg as *const MSG;
+  // Keys go to the focused webview, a child of the window owning the menu.
+  let root = unsafe { GetAncestor((*msg).hwnd, GA_ROOT) } as isize;
+
+  MENU_BARS.with_borrow(|bars| {
+    bars.values().any(|bar| {
+      bar.hwnd == root
+        && unsafe { TranslateAcceleratorW(root as _, bar.menu.haccel() as _, msg) } != 0
+    })
+  })
+}
+
+fn append_items(app: &App, target: &str, submenu: &muda::Submenu, items: &[MenuItem]) {
+  for item in items {
+    let result = match item {
+      MenuItem::Command {
+        command,
+        label,
+        accelerator,
+      } => {
+        let label = label
+          .clone()
+          .or_else(|| command_owner(app, command).and_then(|(_, title)| title))
+          .unwrap_or_else(|| command.clone());
+        let accelerator = accelerator.as_deref().and_then(|accelerator| {
+          accelerator
+            .parse()
+            .map_err(|err| eprintln!("Invalid accelerator {} > {}", accelerator, err))
+            .ok()
+        });
+
+        submenu.append(&muda::MenuItem::with_id(
+          format!("{}/{}/{}", COMMAND_ITEM_PREFIX, target, command),
+          label,
+          true,
+          accelerator,
+        ))
+      }
+      MenuItem::Submenu { label, items } => {
+        let child = muda::Submenu::new(label, true);
+
+        append_items(app, target, &child, items);
+        submenu.append(&child)
+      }
+      MenuItem::Separator => submenu.append(&muda::PredefinedMenuItem::separator()),
+    };
+
+    if let Err(err) = result {
+      eprintln!("Failed to add menu item > {}", err);
+    }
+  }
+}
+
+/// Shows a context menu built from `items` at `position`, or at the cursor
+/// without one, replacing the one already open in the window. `reply` gets the
+/// id of the selected item, or `None` when the menu is dismissed. Must be
+/// called from the event loop thread.
+pub fn popup(
+  window: &AppWindow,
+  items: &[PopupMenuItem],
+  position: Option<PopupPosition>,
+  reply: oneshot::Sender<Option<String>>,
+) {
+  let id = NEXT_POPUP_ID.fetch_add(1, Ordering::Relaxed);
+  let menu = muda::Menu::new();
+
+  for item in popup_items(window.id(), id, items) {
+    if let Err(err) = menu.append(item.as_ref()) {
+      eprintln!("Failed to add menu item > {}", err);
+    }
+  }
+
+  // Replacing the previous popup drops its reply, which resolves it with
+  // `None`.
+  let previous = POPUPS.with_borrow_mut(|popups| {
+    popups.insert(
+      window.id(),
+      Popup {
+        id,
+        menu: menu.clone(),
+        reply,
+      },
+    )
+  });
+
+  drop(previous);
+
+  let position = position.map(|position| {
+    muda::dpi::Position::Logical(muda::dpi::LogicalPosition::new(position.x, position.y))
+  });
+
+  // On Windows and macOS, showing blocks until the menu closes and the
+  // selected item is reported before it returns.
+  #[cfg(target_os = "windows")]
+  {
+    use muda::ContextMenu;
+    use tao::platform::windows::WindowExtWindows;
+
+    unsafe { menu.show_context_menu_for_hwnd(window.tao_window.hwnd(), position) };
+    close_popup(window.id(), id);
+  }
+
+  #[cfg(target_os = "macos")]
+  {
+    use muda::ContextMenu;
+    use tao::platform::macos::WindowExtMacOS;
+
+    unsafe { menu.show_context_menu_for_nsview(window.tao_window.ns_view(), position) };
+    close_popup(window.id(), id);
+  }
+
+  #[cfg(any(target_os = "ios", target_os = "android"))]
+  close_popup(window.id(), id);
+
+  // GTK menus are shown without blocking. They are deactivated before the
+  // selected item is activated, so the dismissal goes through the event loop
+  // to arrive after the selection.
+  #[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "android"
+  )))]
+  {
+    use gtk::prelude::{Cast, MenuShellExt};
+    use muda::ContextMenu;
+    use tao::platform::unix::WindowExtUnix;
+
+    let proxy = window.app.event_loop_proxy.clone();
+    let window_id = window.id();
+
+    menu.gtk_context_menu().connect_deactivate(move |_| {
+      let _ = proxy.send_event(AppWindowEvent::ClosePopup {
+        window_id,
+        popup_id: id,
+      });
+    });
+    menu.show_context_menu_for_gtk_window(window.tao_window.gtk_window().upcast_ref(), position);
+  }
+}
+
+/// Resolves popup `id` of the window with `None` if it is still open. Must be
+/// called from the event loop thread.
+pub fn close_popup(window_id: u32, id: u32) {
+  resolve_popup(window_id, id, None);
+}
+
+fn resolve_popup(window_id: u32, id: u32, item_id: Option<String>) {
+  let popup = POPUPS.with_borrow_mut(|popups| match popups.get(&window_id) {
+    Some(popup) if popup.id == id => popups.remove(&window_id),
+    _ => None,
+  });
+
+  if let Some(popup) = popup {
+    let _ = popup.reply.send(item_id);
+  }
+}
+
+fn popup_items(
+  window_id: u32,
+  popup_id: u32,
+  items: &[PopupMenuItem],
+) -> Vec<Box<dyn muda::IsMenuItem>> {
+  let item_id = |id: &str| format!("{}/{}/{}/{}", POPUP_ITEM_PREFIX, window_id, popup_id, id);
+
+  items
+    .iter()
+    .map(|item| -> Box<dyn muda::IsMenuItem> {
+      match item {
+        PopupMenuItem::Item {
+          id,
+          label,
+          disabled,
+          accelerator,
+        } => Box::new(muda::MenuItem::with_id(
+          item_id(id),
+          label,
+          !disabled,
+          accelerator
+            .as_deref()
+            .and_then(|accelerator| accelerator.parse().ok()),
+        )),
+        PopupMenuItem::Checkbox {
+          id,
+          label,
+          checked,
+          disabled,
+        } => Box::new(muda::CheckMenuItem::with_id(
+          item_id(id),
+          label,
+          !disabled,
+          *checked,
+          None,
+        )),
+        PopupMenuItem::Submenu {
+          label,
+          items,
+          disabled,
+        } => {
+          let submenu = muda::Submenu::new(label, !disabled);
+
+          for child in popup_items(window_id, popup_id, items) {
+            if let Err(err) = submenu.append(child.as_ref()) {
+              eprintln!("Failed to add menu item > {}", err);
+            }
+          }
+
+          Box::new(submenu)
+        }
+        PopupMenuItem::Separator => Box::new(muda::PredefinedMenuItem::separator()),
+      }
+    })
+    .collect()
+}
+
+/// Dispatches a selected native menu item: menu bar items emit
+/// `command.execute` to their window, and context menu items resolve the
+/// popup they belong to.
+pub fn handle_menu_event(app: &App, id: &str) {
+  match id.split_once('/') {
+    Some((COMMAND_ITEM_PREFIX, rest)) => execute_command(app, rest),
+    Some((POPUP_ITEM_PREFIX, rest)) => select_popup_item(rest),
+    _ => {}
+  }
+}
+
+fn select_popup_item(id: &str) {
+  let mut parts = id.splitn(3, '/');
+  let (Some(window_id), Some(popup_id), Some(item_id)) = (
+    parts.next().and_then(|id| id.parse::<u32>().ok()),
+    parts.next().and_then(|id| id.parse::<u32>().ok()),
+    parts.next(),
+  ) else {
+    return;
+  };
+
+  resolve_popup(window_id, popup_id, Some(item_id.to_string()));
+}
+
+/// Emits `command.execute` with the selected command to the window the menu
+/// belongs to, or to the focused one on macOS, naming the extension that
+/// contributed it, if any.
+fn execute_command(app: &App, id: &str) {
+  let Some((target, command)) = id.split_once('/') else {
+    return;
+  };
+
+  #[cfg(target_os = "macos")]
+  let window_id = match target {
+    FOCUSED_WINDOW => focused_window_id(app),
+    _ => target.parse().ok(),
+  };
+
+  #[cfg(not(target_os = "macos"))]
+  let window_id = target.parse::<u32>().ok();
+
+  let Some(window_id) = window_id else {
+    return;
+  };
+  let extension = command_owner(app, command).map(|(extension, _)| extension);
+
+  app.emit_to(
+    "command.execute",
+    json!({ "command": command, "extension": extension, "source": "menu" }),
+    vec![window_id],
+  );
+}
+
+/// The focused window, or the main one when the app isn't active.
+#[cfg(target_os = "macos")]
+fn focused_window_id(app: &App) -> Option<u32> {
+  app
+    .windows
+    .read()
+    .expect("Failed to acquire lock on windows")
+    .values()
+    .find(|window| window.is_focused())
+    .map(|window| window.id())
+    .or_else(|| {
+      *app
+        .main_window_id
+        .read()
+        .expect("Main window id lock is poisoned")
+    })
+}
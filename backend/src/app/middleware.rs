@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use super::{
+  invoke::{InvokeCommand, InvokeError, InvokeResponder},
+  limits::InvokeLimiter,
+  App,
+};
+
+pub type InvokeMiddleware = dyn Fn(InvokeCommand, InvokeResponder, InvokeNext) + Send + Sync + 'static;
+
+/// Remaining middlewares of a dispatch. Calling `run` hands the command to the
+/// next middleware, then to the limiters of the method and to the registered
+/// handler once the chain is exhausted.
+pub struct InvokeNext {
+  app: App,
+  middlewares: Arc<Vec<Arc<InvokeMiddleware>>>,
+  limiters: Arc<Vec<Arc<InvokeLimiter>>>,
+  index: usize,
+}
+
+impl InvokeNext {
+  pub fn new(app: App, method: &str) -> Self {
+    let middlewares = app
+      .invoke_middlewares
+      .read()
+      .expect("Invoke middlewares lock is poisoned")
+      .iter()
+      .filter(|(pattern, _)| matches_method(pattern, method))
+      .map(|(_, middleware)| middleware.clone())
+      .collect();
+
+    Self {
+      limiters: Arc::new(InvokeLimiter::find(&app, method)),
+      app,
+      middlewares: Arc::new(middlewares),
+      index: 0,
+    }
+  }
+
+  pub fn run(self, command: InvokeCommand, responder: InvokeResponder) {
+    let middlewares = self.middlewares.clone();
+    let limiters = self.limiters.clone();
+    let next = Self {
+      index: self.index + 1,
+      ..self
+    };
+
+    if let Some(middleware) = middlewares.get(next.index - 1) {
+      return middleware(command, responder, next);
+    }
+
+    match limiters.get(next.index - 1 - middlewares.len()) {
+      Some(limiter) => limiter.clone().dispatch(command, responder, next),
+      None => {
+        let handler = next
+          .app
+          .invoke_handlers
+          .read()
+          .expect("Invoke handlers lock is poisoned")
+          .get(&command.method)
+          .cloned();
+
+        match handler {
+          Some(handler) => handler(command, responder),
+          None => responder.reply(InvokeError::method_not_found(&command.method).into()),
+        }
+      }
+    }
+  }
+}
+
+/// `*` matches every method, `fs.*` every method under the `fs.` prefix and
+/// anything else only the method with that exact name.
+pub fn matches_method(pattern: &str, method: &str) -> bool {
+  match pattern.strip_suffix('*') {
+    Some(prefix) => method.starts_with(prefix),
+    None => pattern == method,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::matches_method;
+
+  #[test]
+  fn wildcard_matches_every_method() {
+    assert!(matches_method("*", "fs.read"));
+    assert!(matches_method("*", "batch"));
+    assert!(matches_method("*", ""));
+  }
+
+  #[test]
+  fn prefix_matches_methods_under_it() {
+    assert!(matches_method("fs.*", "fs.read"));
+    assert!(matches_method("fs.*", "fs.watch.close"));
+    assert!(matches_method("fs.*", "fs."));
+  }
+
+  #[test]
+  fn prefix_does_not_match_other_methods() {
+    assert!(!matches_method("fs.*", "fs"));
+    assert!(!matches_method("fs.*", "fsx.read"));
+    assert!(!matches_method("fs.*", "window.fs.read"));
+  }
+
+  #[test]
+  fn exact_pattern_matches_only_that_method() {
+    assert!(matches_method("fs.read", "fs.read"));
+    assert!(!matches_method("fs.read", "fs.read_stream"));
+    assert!(!matches_method("fs.read", "fs"));
+  }
+}
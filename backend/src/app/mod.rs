@@ -1,53 +1,127 @@
 use std::{
-  collections::HashMap,
+  collections::{HashMap, HashSet},
   path::PathBuf,
   sync::{Arc, RwLock},
 };
 
 use app_paths::app_data;
-use invoke::{create_ipc_protocol, InvokeCommand, InvokeHandler, InvokeResponder, InvokeResult};
+use channel::{close_window_channels, ChannelEntry};
+use events::{AppEvent, EventListenerEntry};
+use invoke::{
+  create_ipc_protocol, drop_window_streams, CancellationToken, InvokeCommand, InvokeHandler,
+  InvokeRequestKey, InvokeResponder, InvokeStreamEntry,
+};
+use limits::{InvokeLimiter, InvokeLimits};
+use middleware::{InvokeMiddleware, InvokeNext};
 use resources::create_static_protocol;
+use schema::InvokeDescription;
 use tao::{
   event::Event,
-  event_loop::{ControlFlow, EventLoop, EventLoopProxy, EventLoopWindowTarget},
+  event_loop::{ControlFlow, EventLoop, EventLoopClosed, EventLoopProxy, EventLoopWindowTarget},
+};
+use trace::{TraceEntry, TraceRecorder};
+use window::{
+  descendant_ids, AppWindow, AppWindowBuilder, AppWindowEvent, AppWindowExt, ApplicationWindow,
 };
-use window::{AppWindow, AppWindowBuilder, AppWindowEvent, AppWindowExt, ApplicationWindow};
+use window_events::forward_window_event;
 use wry::WebContext;
 
 use crate::state::AppState;
 
 pub mod app_paths;
+#[cfg(debug_assertions)]
+pub mod bridge;
+pub mod channel;
+pub mod close;
+pub mod events;
 pub mod invoke;
+pub mod invoke_args;
+pub mod limits;
+pub mod menu;
+pub mod middleware;
+pub mod monitors;
 pub mod resources;
+pub mod schema;
+pub mod trace;
+pub mod typings;
 pub mod window;
+pub mod window_events;
+pub mod window_state;
 
 pub struct Application<T> {
   pub state: RwLock<T>,
-  pub event_loop_proxy: Arc<EventLoopProxy<AppWindowEvent>>,
+  pub event_loop_proxy: Arc<AppEventLoopProxy>,
   pub windows: RwLock<HashMap<u32, AppWindow>>,
   pub main_window_id: RwLock<Option<u32>>,
   pub static_protocol_folders: RwLock<HashMap<String, PathBuf>>,
   pub invoke_handlers: Arc<RwLock<HashMap<String, Arc<InvokeHandler>>>>,
+  pub invoke_descriptions: RwLock<HashMap<String, InvokeDescription>>,
+  pub invoke_streams: RwLock<HashMap<u32, InvokeStreamEntry>>,
+  pub invoke_requests: RwLock<HashMap<InvokeRequestKey, CancellationToken>>,
+  pub invoke_middlewares: RwLock<Vec<(String, Arc<InvokeMiddleware>)>>,
+  pub invoke_limits: RwLock<Vec<Arc<InvokeLimiter>>>,
+  pub channels: RwLock<HashMap<u32, ChannelEntry>>,
+  pub close_requests: RwLock<HashSet<u32>>,
+  pub event_listeners: RwLock<Vec<EventListenerEntry>>,
+  pub trace: Option<TraceRecorder>,
+  #[cfg(debug_assertions)]
+  pub bridge_clients: RwLock<Vec<bridge::BridgeClient>>,
+  pub menu_bars: RwLock<HashMap<String, Vec<menu::Menu>>>,
   pub import_map: RwLock<HashMap<String, String>>,
 }
 
 pub type App = Arc<Application<AppState>>;
 
+/// Sends events to the event loop. Headless apps have no event loop, so their
+/// events are returned as if it was closed.
+pub struct AppEventLoopProxy(Option<EventLoopProxy<AppWindowEvent>>);
+
+impl AppEventLoopProxy {
+  pub fn send_event(&self, event: AppWindowEvent) -> Result<(), EventLoopClosed<()>> {
+    match &self.0 {
+      Some(proxy) => proxy.send_event(event).map_err(|_| EventLoopClosed(())),
+      None => Err(EventLoopClosed(())),
+    }
+  }
+}
+
 impl Application<AppState> {
   pub fn new(event_loop: &EventLoop<AppWindowEvent>, state: AppState) -> App {
-    let event_loop_proxy = event_loop.create_proxy();
+    Self::with_proxy(Some(event_loop.create_proxy()), state)
+  }
+
+  /// App without an event loop, for tasks that only invoke handlers, like
+  /// emitting typings or replaying a trace. It can run without a display, but
+  /// cannot open windows.
+  pub fn headless(state: AppState) -> App {
+    Self::with_proxy(None, state)
+  }
+
+  fn with_proxy(event_loop_proxy: Option<EventLoopProxy<AppWindowEvent>>, state: AppState) -> App {
     let mut static_protocol_folders = HashMap::new();
 
     static_protocol_folders.insert("app".to_string(), app_paths::resources_dir().join("www"));
     static_protocol_folders.insert("esm".to_string(), app_paths::resources_dir().join("esm"));
 
     Arc::new(Self {
-      event_loop_proxy: Arc::new(event_loop_proxy),
+      event_loop_proxy: Arc::new(AppEventLoopProxy(event_loop_proxy)),
       windows: RwLock::new(HashMap::new()),
       invoke_handlers: Arc::new(RwLock::new(HashMap::new())),
+      invoke_descriptions: RwLock::new(HashMap::new()),
+      invoke_streams: RwLock::new(HashMap::new()),
+      invoke_requests: RwLock::new(HashMap::new()),
+      invoke_middlewares: RwLock::new(Vec::new()),
+      invoke_limits: RwLock::new(Vec::new()),
+      channels: RwLock::new(HashMap::new()),
+      close_requests: RwLock::new(HashSet::new()),
+      event_listeners: RwLock::new(Vec::new()),
+      trace: TraceRecorder::from_env(),
+      #[cfg(debug_assertions)]
+      bridge_clients: RwLock::new(Vec::new()),
       main_window_id: RwLock::new(None),
       static_protocol_folders: RwLock::new(static_protocol_folders),
       state: RwLock::new(state),
+      menu_bars: RwLock::new(HashMap::new()),
       import_map: RwLock::new(HashMap::new()),
     })
   }
@@ -59,7 +133,21 @@ pub trait AppExt {
   where
     F: Fn(InvokeCommand, InvokeResponder) + Send + Sync + 'static;
   fn remove_invoke_handler(&self, method: &str);
+  fn describe_invoke_handler(&self, method: &str, description: InvokeDescription);
+  fn add_invoke_middleware<F>(&self, pattern: &str, middleware: F)
+  where
+    F: Fn(InvokeCommand, InvokeResponder, InvokeNext) + Send + Sync + 'static;
+  fn set_invoke_limits(&self, pattern: &str, limits: InvokeLimits);
   fn emit(&self, name: &str, payload: serde_json::Value);
+  fn emit_to(&self, name: &str, payload: serde_json::Value, target: Vec<u32>);
+  fn on<F>(&self, pattern: &str, listener: F) -> u32
+  where
+    F: Fn(&AppEvent) + Send + Sync + 'static;
+  fn once<F>(&self, pattern: &str, listener: F) -> u32
+  where
+    F: FnOnce(&AppEvent) + Send + 'static;
+  fn off(&self, id: u32) -> bool;
+  fn set_menu_bar(&self, id: &str, menus: Vec<menu::Menu>);
   fn build_window(&self) -> AppWindowBuilder;
   fn invoke(&self, command: InvokeCommand, responder: InvokeResponder);
   fn handle_event(
@@ -89,15 +177,75 @@ impl AppExt for App {
       .cloned()
       .collect();
 
+    self.emit_to(name, payload, targets);
+  }
+
+  /// Notifies the Rust listeners, then dispatches the event to the `target`
+  /// windows.
+  fn emit_to(&self, name: &str, payload: serde_json::Value, target: Vec<u32>) {
+    let event = AppEvent {
+      name: name.to_string(),
+      payload,
+      target,
+    };
+
+    events::notify(self, &event);
+
     self
       .event_loop_proxy
       .send_event(AppWindowEvent::Event {
-        name: name.to_string(),
-        payload,
-        target: targets,
+        name: event.name,
+        payload: event.payload,
+        target: event.target,
       })
       .expect("Failed to send event");
   }
+
+  /// `pattern` is an event name, `prefix.*` or `*`. Returns an id for `off`.
+  fn on<F>(&self, pattern: &str, listener: F) -> u32
+  where
+    F: Fn(&AppEvent) + Send + Sync + 'static,
+  {
+    events::add_listener(self, pattern, false, Arc::new(listener))
+  }
+
+  fn once<F>(&self, pattern: &str, listener: F) -> u32
+  where
+    F: FnOnce(&AppEvent) + Send + 'static,
+  {
+    let listener = std::sync::Mutex::new(Some(listener));
+
+    events::add_listener(
+      self,
+      pattern,
+      true,
+      Arc::new(move |event| {
+        if let Some(listener) = listener
+          .lock()
+          .expect("Event listener lock is poisoned")
+          .take()
+        {
+          listener(event);
+        }
+      }),
+    )
+  }
+
+  fn off(&self, id: u32) -> bool {
+    events::remove_listener(self, id)
+  }
+
+  /// Replaces the model of menu bar `id`, updating the windows showing it.
+  fn set_menu_bar(&self, id: &str, menus: Vec<menu::Menu>) {
+    self
+      .menu_bars
+      .write()
+      .expect("Menu bars lock is poisoned")
+      .insert(id.to_string(), menus);
+
+    menu::refresh(self);
+  }
+
   fn build_window(&self) -> AppWindowBuilder {
     AppWindowBuilder::new(self.clone())
       .with_protocol("lenz", create_static_protocol(self.clone()))
@@ -105,20 +253,7 @@ impl AppExt for App {
   }
 
   fn invoke(&self, invoke_request: InvokeCommand, responder: InvokeResponder) {
-    let method = invoke_request.method.clone();
-
-    match self
-      .invoke_handlers
-      .read()
-      .expect("Invoke handlers lock is poisoned")
-      .get(&method)
-    {
-      Some(handler) => handler(invoke_request, responder),
-      None => responder.reply(InvokeResult::error(&format!(
-        "No handler found for method: {}",
-        method
-      ))),
-    }
+    InvokeNext::new(self.clone(), &invoke_request.method).run(invoke_request, responder);
   }
 
   fn add_invoke_handler<F>(&self, method: &str, handler: F)
@@ -135,18 +270,55 @@ impl AppExt for App {
       );
   }
 
+  fn add_invoke_middleware<F>(&self, pattern: &str, middleware: F)
+  where
+    F: Fn(InvokeCommand, InvokeResponder, InvokeNext) + Send + Sync + 'static,
+  {
+    self
+      .invoke_middlewares
+      .write()
+      .expect("Invoke middlewares lock is poisoned")
+      .push((pattern.to_string(), Arc::new(middleware)));
+  }
+
+  /// Replaces the limits previously set for the same pattern. Invokes already
+  /// running keep the limits they were dispatched with.
+  fn set_invoke_limits(&self, pattern: &str, limits: InvokeLimits) {
+    let mut invoke_limits = self
+      .invoke_limits
+      .write()
+      .expect("Invoke limits lock is poisoned");
+
+    invoke_limits.retain(|limiter| limiter.pattern() != pattern);
+    invoke_limits.push(Arc::new(InvokeLimiter::new(pattern, limits)));
+  }
+
   fn remove_invoke_handler(&self, method: &str) {
     self
       .invoke_handlers
       .write()
       .expect("Invoke handlers lock is poisoned")
       .remove(method);
+
+    self
+      .invoke_descriptions
+      .write()
+      .expect("Invoke descriptions lock is poisoned")
+      .remove(method);
+  }
+
+  fn describe_invoke_handler(&self, method: &str, description: InvokeDescription) {
+    self
+      .invoke_descriptions
+      .write()
+      .expect("Invoke descriptions lock is poisoned")
+      .insert(method.to_string(), description);
   }
 
   fn handle_event(
     &self,
     event: Event<'_, AppWindowEvent>,
-    _event_loop: &EventLoopWindowTarget<AppWindowEvent>,
+    event_loop: &EventLoopWindowTarget<AppWindowEvent>,
     control_flow: &mut ControlFlow,
   ) {
     *control_flow = ControlFlow::Wait;
@@ -155,23 +327,25 @@ impl AppExt for App {
         window_id,
         event: tao::event::WindowEvent::CloseRequested,
         ..
+      } => {
+        close::request_close(self.clone(), ApplicationWindow::window_id_to_u32(window_id));
+      }
+      Event::WindowEvent {
+        window_id,
+        event,
+        ..
       } => {
         let window_id = ApplicationWindow::window_id_to_u32(window_id);
-        let mut windows = self
-          .windows
-          .write()
-          .expect("Failed to acquire lock on windows");
 
-        if *self.main_window_id.read().expect(
-        "Failed to acquire lock on main window id. This should never happen as the lock is poisoned",
-      ) == Some(window_id) {
-        windows.clear();
-      } else {
-        windows.remove(&window_id);
-      }
+        if let Some(window) = self.get_window(window_id) {
+          // A window blocked by a modal child hands the focus over to it.
+          if let tao::event::WindowEvent::Focused(true) = event {
+            if let Some(modal) = window.children().into_iter().find(|child| child.modal) {
+              modal.focus();
+            }
+          }
 
-        if windows.is_empty() {
-          *control_flow = ControlFlow::Exit;
+          forward_window_event(&window, &event);
         }
       }
       Event::UserEvent(AppWindowEvent::Event {
@@ -179,13 +353,133 @@ impl AppExt for App {
         payload,
         target,
       }) => {
+        if let Some(trace) = &self.trace {
+          trace.record(TraceEntry::Event {
+            name: name.clone(),
+            payload: payload.clone(),
+            target: target.clone(),
+          });
+        }
+
+        #[cfg(debug_assertions)]
+        bridge::dispatch(self, &name, &payload, &target);
+
         for window_id in target {
           if let Some(window) = self.get_window(window_id) {
-            window.eval(&format!(
-              "window.__dispatch({}, {});",
-              serde_json::to_string(&name).unwrap(),
-              serde_json::to_string(&payload).unwrap()
-            ));
+            window.dispatch(&name, &payload);
+          }
+        }
+      }
+      Event::UserEvent(AppWindowEvent::Close { window_id }) => {
+        let is_main = *self
+          .main_window_id
+          .read()
+          .expect("Main window id lock is poisoned")
+          == Some(window_id);
+        let mut windows = self
+          .windows
+          .write()
+          .expect("Failed to acquire lock on windows");
+
+        // Closing the main window closes every window, and any other window
+        // closes along with its children.
+        let ids = if is_main {
+          windows.keys().cloned().collect()
+        } else if windows.contains_key(&window_id) {
+          let mut ids = descendant_ids(&windows, window_id);
+          ids.push(window_id);
+          ids
+        } else {
+          Vec::new()
+        };
+        let closed: Vec<AppWindow> = ids.iter().filter_map(|id| windows.remove(id)).collect();
+        let is_empty = windows.is_empty();
+
+        drop(windows);
+
+        for window in closed.iter() {
+          window_state::save(window);
+          close_window_channels(self, window.id());
+          drop_window_streams(self, window.id());
+          window.cancel_calls("Window was closed");
+          menu::uninstall(window.id());
+
+          if let (true, Some(parent)) = (window.modal, window.parent()) {
+            window::end_modal(window, &parent);
+
+            if !parent.children().iter().any(|child| child.modal) {
+              parent.set_input_enabled(true);
+              parent.focus();
+            }
+          }
+        }
+
+        if is_empty {
+          *control_flow = ControlFlow::Exit;
+        }
+      }
+      Event::UserEvent(AppWindowEvent::CreateWindow { options, reply }) => {
+        let builder = match options.parent.and_then(|id| self.get_window(id)) {
+          Some(parent) => parent.build_window(),
+          None => self.build_window(),
+        };
+        let window = builder.with_options(options).try_build(event_loop);
+
+        let _ = reply.send(window.map(|window| window.id()));
+      }
+      Event::UserEvent(AppWindowEvent::UpdateMenus) => menu::update(self),
+      Event::UserEvent(AppWindowEvent::Popup {
+        window_id,
+        items,
+        position,
+        reply,
+      }) => {
+        if let Some(window) = self.get_window(window_id) {
+          menu::popup(&window, &items, position, reply);
+        }
+      }
+      Event::UserEvent(AppWindowEvent::Drag {
+        window_id,
+        direction,
+        reply,
+      }) => {
+        let result = match self.get_window(window_id) {
+          Some(window) => match direction {
+            Some(direction) => window.drag_resize(direction),
+            None => window.drag(),
+          }
+          .map_err(|err| err.to_string()),
+          None => Err("Window not found".to_string()),
+        };
+
+        let _ = reply.send(result);
+      }
+      Event::UserEvent(AppWindowEvent::RunOnMainThread { task }) => task.run(),
+      Event::UserEvent(AppWindowEvent::ClosePopup {
+        window_id,
+        popup_id,
+      }) => {
+        menu::close_popup(window_id, popup_id);
+      }
+      // The previous page is gone, along with everything it was pulling.
+      Event::UserEvent(AppWindowEvent::PageLoadStarted { window_id }) => {
+        drop_window_streams(self, window_id);
+        close_window_channels(self, window_id);
+
+        if let Some(window) = self.get_window(window_id) {
+          window.cancel_calls("Page was reloaded");
+        }
+      }
+      Event::UserEvent(AppWindowEvent::PageReady {
+        window_id,
+        generation,
+      }) => {
+        if let Some(window) = self.get_window(window_id) {
+          window.set_page_ready(generation);
+
+          // A reload drops the inert state of a window blocked by a modal child.
+          if window.children().iter().any(|child| child.modal) {
+            window.set_input_enabled(false);
           }
         }
       }
@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use tao::{
+  dpi::{PhysicalPosition, PhysicalSize},
+  monitor::MonitorHandle,
+  window::Window,
+};
+
+/// A rectangle in physical pixels, in the desktop coordinate space shared by
+/// all monitors.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Rect {
+  pub x: i32,
+  pub y: i32,
+  pub width: u32,
+  pub height: u32,
+}
+
+/// A connected monitor. `index` identifies it in the `monitor.*` and
+/// `window.*` methods for as long as the monitor setup doesn't change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorInfo {
+  pub index: usize,
+  pub name: Option<String>,
+  pub primary: bool,
+  pub scale_factor: f64,
+  pub bounds: Rect,
+  /// Part of the monitor not covered by taskbars, docks and panels.
+  pub work_area: Rect,
+}
+
+pub fn bounds(monitor: &MonitorHandle) -> Rect {
+  let PhysicalPosition { x, y } = monitor.position();
+  let PhysicalSize { width, height } = monitor.size();
+
+  Rect {
+    x,
+    y,
+    width,
+    height,
+  }
+}
+
+/// Part of `monitor` not covered by taskbars, docks and panels, or the whole
+/// monitor when the platform doesn't tell.
+pub fn work_area(monitor: &MonitorHandle) -> Rect {
+  platform_work_area(monitor).unwrap_or_else(|| bounds(monitor))
+}
+
+#[cfg(target_os = "windows")]
+fn platform_work_area(monitor: &MonitorHandle) -> Option<Rect> {
+  use tao::platform::windows::MonitorHandleExtWindows;
+  use windows_sys::Win32::Graphics::Gdi::{GetMonitorInfoW, MONITORINFO};
+
+  let mut info: MONITORINFO = unsafe { std::mem::zeroed() };
+  info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+
+  if unsafe { GetMonitorInfoW(monitor.hmonitor() as _, &mut info) } == 0 {
+    return None;
+  }
+
+  let area = info.rcWork;
+
+  Some(Rect {
+    x: area.left,
+    y: area.top,
+    width: (area.right - area.left) as u32,
+    height: (area.bottom - area.top) as u32,
+  })
+}
+
+/// `visibleFrame` is in points from the bottom-left corner of the main screen,
+/// so its insets within `frame` are applied to the bounds tao reports.
+#[cfg(target_os = "macos")]
+fn platform_work_area(monitor: &MonitorHandle) -> Option<Rect> {
+  use cocoa::{appkit::NSScreen, base::id};
+  use tao::platform::macos::MonitorHandleExtMacOS;
+
+  let screen = monitor.ns_screen()? as id;
+  let (frame, visible) = unsafe { (NSScreen::frame(screen), NSScreen::visibleFrame(screen)) };
+  let scale = monitor.scale_factor();
+  let left = (visible.origin.x - frame.origin.x) * scale;
+  let bottom = (visible.origin.y - frame.origin.y) * scale;
+  let top = (frame.origin.y + frame.size.height - visible.origin.y - visible.size.height) * scale;
+  let bounds = bounds(monitor);
+
+  Some(Rect {
+    x: bounds.x + left as i32,
+    y: bounds.y + top as i32,
+    width: (visible.size.width * scale) as u32,
+    height: (bounds.height as f64 - top - bottom) as u32,
+  })
+}
+
+/// GDK works in logical pixels, the monitor is found by its logical origin.
+#[cfg(any(
+  target_os = "linux",
+  target_os = "dragonfly",
+  target_os = "freebsd",
+  target_os = "openbsd",
+  target_os = "netbsd"
+))]
+fn platform_work_area(monitor: &MonitorHandle) -> Option<Rect> {
+  use gtk::gdk::{self, prelude::MonitorExt};
+  use tao::dpi::{LogicalPosition, LogicalSize};
+
+  let display = gdk::Display::default()?;
+  let scale = monitor.scale_factor();
+  let position = monitor.position();
+  let area = (0..display.n_monitors())
+    .filter_map(|index| display.monitor(index))
+    .find(|other| {
+      let geometry = other.geometry();
+
+      LogicalPosition::new(geometry.x(), geometry.y()).to_physical::<i32>(scale) == position
+    })?
+    .workarea();
+  let PhysicalPosition { x, y } =
+    LogicalPosition::new(area.x(), area.y()).to_physical::<i32>(scale);
+  let PhysicalSize { width, height } =
+    LogicalSize::new(area.width() as u32, area.height() as u32).to_physical::<u32>(scale);
+
+  Some(Rect {
+    x,
+    y,
+    width,
+    height,
+  })
+}
+
+#[cfg(not(any(
+  target_os = "windows",
+  target_os = "macos",
+  target_os = "linux",
+  target_os = "dragonfly",
+  target_os = "freebsd",
+  target_os = "openbsd",
+  target_os = "netbsd"
+)))]
+fn platform_work_area(_monitor: &MonitorHandle) -> Option<Rect> {
+  None
+}
+
+/// Must be called from the event loop thread, like the other monitor queries.
+pub fn list(window: &Window) -> Vec<MonitorInfo> {
+  let primary = window.primary_monitor();
+
+  window
+    .available_monitors()
+    .enumerate()
+    .map(|(index, monitor)| MonitorInfo {
+      index,
+      name: monitor.name(),
+      primary: primary.as_ref() == Some(&monitor),
+      scale_factor: monitor.scale_factor(),
+      bounds: bounds(&monitor),
+      work_area: work_area(&monitor),
+    })
+    .collect()
+}
+
+pub fn get(window: &Window, index: usize) -> Option<MonitorHandle> {
+  window.available_monitors().nth(index)
+}
+
+pub fn index_of(window: &Window, monitor: &MonitorHandle) -> Option<usize> {
+  window
+    .available_monitors()
+    .position(|other| &other == monitor)
+}
+
+/// Position centering a window of `size` in the work area of `monitor`. A
+/// window larger than the work area is aligned to its top-left corner instead.
+pub fn centered_position(
+  monitor: &MonitorHandle,
+  size: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+  let area = work_area(monitor);
+
+  PhysicalPosition::new(
+    area.x + (area.width.saturating_sub(size.width) / 2) as i32,
+    area.y + (area.height.saturating_sub(size.height) / 2) as i32,
+  )
+}
+
+/// Closest position to `position` that keeps a window of `size` in the work
+/// area of `monitor`.
+pub fn clamped_position(
+  monitor: &MonitorHandle,
+  position: PhysicalPosition<i32>,
+  size: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+  clamp_position(work_area(monitor), position, size)
+}
+
+/// Closest position to `position` that keeps a window of `size` in `area`.
+/// A window larger than the area is aligned to its top-left corner.
+pub fn clamp_position(
+  area: Rect,
+  position: PhysicalPosition<i32>,
+  size: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+  let max_x = area.x + area.width.saturating_sub(size.width) as i32;
+  let max_y = area.y + area.height.saturating_sub(size.height) as i32;
+
+  PhysicalPosition::new(
+    position.x.clamp(area.x, max_x),
+    position.y.clamp(area.y, max_y),
+  )
+}
@@ -0,0 +1,805 @@
+use std::marker::PhantomData;
+
+use serde::{
+  de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor},
+  Deserialize, Serialize,
+};
+
+use super::App;
+
+const MAX_DEPTH: usize = 16;
+const BINARY_MARKER: &str = "$lenz::Binary";
+const STREAM_MARKER: &str = "$lenz::Stream";
+const SUBSCRIPTION_MARKER: &str = "$lenz::Subscription";
+
+/// Shape of a value exchanged over IPC, as reported by `ipc.describe`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Schema {
+  Any,
+  Null,
+  Boolean,
+  Number,
+  String,
+  Binary,
+  Stream,
+  Subscription {
+    message: Box<Schema>,
+  },
+  Optional {
+    inner: Box<Schema>,
+  },
+  Array {
+    items: Box<Schema>,
+  },
+  Tuple {
+    items: Vec<Schema>,
+  },
+  Record {
+    values: Box<Schema>,
+  },
+  Object {
+    name: String,
+    fields: Vec<SchemaField>,
+  },
+  Enum {
+    name: String,
+    variants: Vec<String>,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaField {
+  pub name: String,
+  pub schema: Schema,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InvokeDescription {
+  /// `None` for handlers taking the raw `InvokeCommand`.
+  pub args: Option<Vec<Schema>>,
+  pub returns: Schema,
+}
+
+/// Marker types for return annotations of handlers that do not reply with JSON.
+#[derive(Default)]
+pub struct Binary;
+#[derive(Default)]
+pub struct Stream;
+pub struct Subscription<T>(PhantomData<T>);
+
+/// Describes `T` by recording which `Deserializer` methods its `Deserialize`
+/// implementation calls. Parts that cannot be traced are reported as `Any`.
+pub fn schema_of<T: DeserializeOwned>() -> Schema {
+  let mut schema = Schema::Any;
+  let _ = T::deserialize(Tracer {
+    schema: &mut schema,
+    depth: 0,
+  });
+
+  schema
+}
+
+impl Schema {
+  fn is_optional(&self) -> bool {
+    matches!(self, Schema::Optional { .. } | Schema::Any)
+  }
+
+  pub fn to_typescript(&self) -> String {
+    match self {
+      Schema::Any => "unknown".to_string(),
+      Schema::Null => "null".to_string(),
+      Schema::Boolean => "boolean".to_string(),
+      Schema::Number => "number".to_string(),
+      Schema::String => "string".to_string(),
+      Schema::Binary => "ArrayBuffer".to_string(),
+      Schema::Stream => "InvokeStream".to_string(),
+      Schema::Subscription { message } => format!("ChannelRef<{}>", message.to_typescript()),
+      Schema::Optional { inner } => format!("{} | null", inner.to_typescript()),
+      Schema::Array { items } => match items.as_ref() {
+        Schema::Optional { .. } | Schema::Enum { .. } => format!("({})[]", items.to_typescript()),
+        items => format!("{}[]", items.to_typescript()),
+      },
+      Schema::Tuple { items } => format!(
+        "[{}]",
+        items
+          .iter()
+          .map(Schema::to_typescript)
+          .collect::<Vec<_>>()
+          .join(", ")
+      ),
+      Schema::Record { values } => format!("Record<string, {}>", values.to_typescript()),
+      Schema::Object { fields, .. } => format!(
+        "{{ {} }}",
+        fields
+          .iter()
+          .map(|field| format!(
+            "{}{}: {};",
+            field.name,
+            if field.schema.is_optional() { "?" } else { "" },
+            field.schema.to_typescript()
+          ))
+          .collect::<Vec<_>>()
+          .join(" ")
+      ),
+      Schema::Enum { variants, .. } => variants
+        .iter()
+        .map(|variant| format!("'{}'", variant))
+        .collect::<Vec<_>>()
+        .join(" | "),
+    }
+  }
+
+  /// Positional parameters of an invoke; trailing optional ones can be omitted.
+  pub fn args_to_typescript(args: &Option<Vec<Schema>>) -> String {
+    let args = match args {
+      Some(args) => args,
+      None => return "unknown[]".to_string(),
+    };
+    let required = args
+      .iter()
+      .rposition(|arg| !arg.is_optional())
+      .map_or(0, |index| index + 1);
+
+    format!(
+      "[{}]",
+      args
+        .iter()
+        .enumerate()
+        .map(|(index, arg)| format!(
+          "arg{}{}: {}",
+          index,
+          if index >= required { "?" } else { "" },
+          arg.to_typescript()
+        ))
+        .collect::<Vec<_>>()
+        .join(", ")
+    )
+  }
+}
+
+#[derive(Debug)]
+struct TraceError(String);
+
+impl std::fmt::Display for TraceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+  fn custom<T: std::fmt::Display>(msg: T) -> Self {
+    Self(msg.to_string())
+  }
+}
+
+struct Tracer<'a> {
+  schema: &'a mut Schema,
+  depth: usize,
+}
+
+impl<'a> Tracer<'a> {
+  fn child<'b>(&self, schema: &'b mut Schema) -> Result<Tracer<'b>, TraceError> {
+    if self.depth >= MAX_DEPTH {
+      return Err(de::Error::custom("Schema is too deep"));
+    }
+
+    Ok(Tracer {
+      schema,
+      depth: self.depth + 1,
+    })
+  }
+}
+
+macro_rules! trace_primitive {
+  ($($method:ident => $schema:expr, $visit:ident($($value:expr)?);)*) => {
+    $(
+      fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        *self.schema = $schema;
+        visitor.$visit($($value)?)
+      }
+    )*
+  };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Tracer<'a> {
+  type Error = TraceError;
+
+  trace_primitive! {
+    deserialize_any => Schema::Any, visit_unit();
+    deserialize_ignored_any => Schema::Any, visit_unit();
+    deserialize_bool => Schema::Boolean, visit_bool(false);
+    deserialize_i8 => Schema::Number, visit_i8(0);
+    deserialize_i16 => Schema::Number, visit_i16(0);
+    deserialize_i32 => Schema::Number, visit_i32(0);
+    deserialize_i64 => Schema::Number, visit_i64(0);
+    deserialize_u8 => Schema::Number, visit_u8(0);
+    deserialize_u16 => Schema::Number, visit_u16(0);
+    deserialize_u32 => Schema::Number, visit_u32(0);
+    deserialize_u64 => Schema::Number, visit_u64(0);
+    deserialize_f32 => Schema::Number, visit_f32(0.0);
+    deserialize_f64 => Schema::Number, visit_f64(0.0);
+    deserialize_char => Schema::String, visit_char('a');
+    deserialize_str => Schema::String, visit_str("");
+    deserialize_string => Schema::String, visit_string(String::new());
+    deserialize_identifier => Schema::String, visit_str("");
+    deserialize_bytes => Schema::Binary, visit_bytes(&[]);
+    deserialize_byte_buf => Schema::Binary, visit_byte_buf(Vec::new());
+    deserialize_unit => Schema::Null, visit_unit();
+  }
+
+  fn deserialize_unit_struct<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    self.deserialize_unit(visitor)
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    let mut inner = Schema::Any;
+    let result = visitor.visit_some(self.child(&mut inner)?);
+
+    *self.schema = Schema::Optional {
+      inner: Box::new(inner),
+    };
+
+    result
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    match name {
+      BINARY_MARKER => *self.schema = Schema::Binary,
+      STREAM_MARKER => *self.schema = Schema::Stream,
+      SUBSCRIPTION_MARKER => {
+        let mut message = Schema::Any;
+        let result = visitor.visit_newtype_struct(self.child(&mut message)?);
+
+        *self.schema = Schema::Subscription {
+          message: Box::new(message),
+        };
+
+        return result;
+      }
+      _ => return visitor.visit_newtype_struct(self),
+    }
+
+    visitor.visit_unit()
+  }
+
+  fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    let mut items = Schema::Any;
+    let result = visitor.visit_seq(SeqTracer {
+      tracer: Some(self.child(&mut items)?),
+    });
+
+    *self.schema = Schema::Array {
+      items: Box::new(items),
+    };
+
+    result
+  }
+
+  fn deserialize_tuple<V: Visitor<'de>>(
+    self,
+    len: usize,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    let mut items = vec![Schema::Any; len];
+    let depth = self.depth + 1;
+    let result = visitor.visit_seq(TupleTracer {
+      items: items.iter_mut(),
+      depth,
+    });
+
+    *self.schema = Schema::Tuple { items };
+
+    result
+  }
+
+  fn deserialize_tuple_struct<V: Visitor<'de>>(
+    self,
+    _name: &'static str,
+    len: usize,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    self.deserialize_tuple(len, visitor)
+  }
+
+  fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+    let mut key = Schema::Any;
+    let mut values = Schema::Any;
+    let result = visitor.visit_map(MapTracer {
+      key: Some(self.child(&mut key)?),
+      value: Some(Tracer {
+        schema: &mut values,
+        depth: self.depth + 1,
+      }),
+    });
+
+    *self.schema = Schema::Record {
+      values: Box::new(values),
+    };
+
+    result
+  }
+
+  fn deserialize_struct<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    let mut schemas = vec![Schema::Any; fields.len()];
+    let depth = self.depth + 1;
+
+    if depth > MAX_DEPTH {
+      return Err(de::Error::custom("Schema is too deep"));
+    }
+
+    let result = visitor.visit_map(StructTracer {
+      fields: fields.iter(),
+      schemas: schemas.iter_mut(),
+      value: None,
+      depth,
+    });
+
+    *self.schema = Schema::Object {
+      name: name.to_string(),
+      fields: fields
+        .iter()
+        .zip(schemas)
+        .map(|(name, schema)| SchemaField {
+          name: name.to_string(),
+          schema,
+        })
+        .collect(),
+    };
+
+    result
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    *self.schema = Schema::Enum {
+      name: name.to_string(),
+      variants: variants.iter().map(|variant| variant.to_string()).collect(),
+    };
+
+    let variant = variants
+      .first()
+      .ok_or_else(|| de::Error::custom("Enum has no variants"))?;
+
+    visitor.visit_enum(EnumTracer { variant })
+  }
+}
+
+struct SeqTracer<'a> {
+  tracer: Option<Tracer<'a>>,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for SeqTracer<'a> {
+  type Error = TraceError;
+
+  fn next_element_seed<T: DeserializeSeed<'de>>(
+    &mut self,
+    seed: T,
+  ) -> Result<Option<T::Value>, Self::Error> {
+    match self.tracer.take() {
+      Some(tracer) => seed.deserialize(tracer).map(Some),
+      None => Ok(None),
+    }
+  }
+}
+
+struct TupleTracer<'a> {
+  items: std::slice::IterMut<'a, Schema>,
+  depth: usize,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for TupleTracer<'a> {
+  type Error = TraceError;
+
+  fn next_element_seed<T: DeserializeSeed<'de>>(
+    &mut self,
+    seed: T,
+  ) -> Result<Option<T::Value>, Self::Error> {
+    match self.items.next() {
+      Some(schema) => seed
+        .deserialize(Tracer {
+          schema,
+          depth: self.depth,
+        })
+        .map(Some),
+      None => Ok(None),
+    }
+  }
+}
+
+struct MapTracer<'a, 'b> {
+  key: Option<Tracer<'a>>,
+  value: Option<Tracer<'b>>,
+}
+
+impl<'de, 'a, 'b> de::MapAccess<'de> for MapTracer<'a, 'b> {
+  type Error = TraceError;
+
+  fn next_key_seed<K: DeserializeSeed<'de>>(
+    &mut self,
+    seed: K,
+  ) -> Result<Option<K::Value>, Self::Error> {
+    match self.key.take() {
+      Some(tracer) => seed.deserialize(tracer).map(Some),
+      None => Ok(None),
+    }
+  }
+
+  fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+    match self.value.take() {
+      Some(tracer) => seed.deserialize(tracer),
+      None => Err(de::Error::custom("Map value requested twice")),
+    }
+  }
+}
+
+struct StructTracer<'a> {
+  fields: std::slice::Iter<'static, &'static str>,
+  schemas: std::slice::IterMut<'a, Schema>,
+  value: Option<&'a mut Schema>,
+  depth: usize,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for StructTracer<'a> {
+  type Error = TraceError;
+
+  fn next_key_seed<K: DeserializeSeed<'de>>(
+    &mut self,
+    seed: K,
+  ) -> Result<Option<K::Value>, Self::Error> {
+    match (self.fields.next(), self.schemas.next()) {
+      (Some(field), Some(schema)) => {
+        self.value = Some(schema);
+        seed.deserialize(field.into_deserializer()).map(Some)
+      }
+      _ => Ok(None),
+    }
+  }
+
+  fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+    match self.value.take() {
+      Some(schema) => seed.deserialize(Tracer {
+        schema,
+        depth: self.depth,
+      }),
+      None => Err(de::Error::custom("Struct value requested before its key")),
+    }
+  }
+}
+
+struct EnumTracer {
+  variant: &'static str,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumTracer {
+  type Error = TraceError;
+  type Variant = Self;
+
+  fn variant_seed<V: DeserializeSeed<'de>>(
+    self,
+    seed: V,
+  ) -> Result<(V::Value, Self::Variant), Self::Error> {
+    let value = seed.deserialize(self.variant.into_deserializer())?;
+
+    Ok((value, self))
+  }
+}
+
+impl<'de> de::VariantAccess<'de> for EnumTracer {
+  type Error = TraceError;
+
+  fn unit_variant(self) -> Result<(), Self::Error> {
+    Ok(())
+  }
+
+  fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Self::Error> {
+    seed.deserialize(Tracer {
+      schema: &mut Schema::Any,
+      depth: MAX_DEPTH,
+    })
+  }
+
+  fn tuple_variant<V: Visitor<'de>>(
+    self,
+    _len: usize,
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    visitor.visit_seq(SeqTracer { tracer: None })
+  }
+
+  fn struct_variant<V: Visitor<'de>>(
+    self,
+    _fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Self::Error> {
+    visitor.visit_map(MapTracer {
+      key: None,
+      value: None,
+    })
+  }
+}
+
+struct UnitVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Default> Visitor<'de> for UnitVisitor<T> {
+  type Value = T;
+
+  fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(formatter, "a return marker")
+  }
+
+  fn visit_unit<E: de::Error>(self) -> Result<T, E> {
+    Ok(T::default())
+  }
+
+  fn visit_newtype_struct<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+    de::IgnoredAny::deserialize(deserializer).map(|_| T::default())
+  }
+}
+
+impl<T> Default for Subscription<T> {
+  fn default() -> Self {
+    Subscription(PhantomData)
+  }
+}
+
+impl<'de> Deserialize<'de> for Binary {
+  fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserializer.deserialize_newtype_struct(BINARY_MARKER, UnitVisitor(PhantomData))
+  }
+}
+
+impl<'de> Deserialize<'de> for Stream {
+  fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserializer.deserialize_newtype_struct(STREAM_MARKER, UnitVisitor(PhantomData))
+  }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Subscription<T> {
+  fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct SubscriptionVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for SubscriptionVisitor<T> {
+      type Value = Subscription<T>;
+
+      fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a channel marker")
+      }
+
+      fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+      ) -> Result<Self::Value, D::Error> {
+        T::deserialize(deserializer).map(|_| Subscription(PhantomData))
+      }
+    }
+
+    deserializer.deserialize_newtype_struct(SUBSCRIPTION_MARKER, SubscriptionVisitor(PhantomData))
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InvokeMethodDescription {
+  pub name: String,
+  #[serde(flatten)]
+  pub description: InvokeDescription,
+}
+
+/// Registered invoke methods sorted by name. Methods added with
+/// `add_invoke_handler` directly are listed with unknown arguments.
+pub fn describe_invoke_methods(app: &App) -> Vec<InvokeMethodDescription> {
+  let descriptions = app
+    .invoke_descriptions
+    .read()
+    .expect("Invoke descriptions lock is poisoned");
+  let mut methods: Vec<InvokeMethodDescription> = app
+    .invoke_handlers
+    .read()
+    .expect("Invoke handlers lock is poisoned")
+    .keys()
+    .map(|name| InvokeMethodDescription {
+      name: name.clone(),
+      description: descriptions
+        .get(name)
+        .cloned()
+        .unwrap_or(InvokeDescription {
+          args: None,
+          returns: Schema::Any,
+        }),
+    })
+    .collect();
+
+  methods.sort_by(|a, b| a.name.cmp(&b.name));
+  methods
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::*;
+
+  #[derive(Deserialize)]
+  #[allow(dead_code)]
+  #[serde(rename_all = "camelCase")]
+  struct Options {
+    file_name: String,
+    size: Option<u64>,
+    tags: Vec<String>,
+    mode: Mode,
+  }
+
+  #[derive(Deserialize)]
+  #[allow(dead_code)]
+  #[serde(rename_all = "lowercase")]
+  enum Mode {
+    Read,
+    Write,
+  }
+
+  #[derive(Deserialize)]
+  #[allow(dead_code)]
+  struct Node {
+    children: Vec<Node>,
+  }
+
+  fn field(name: &str, schema: Schema) -> SchemaField {
+    SchemaField {
+      name: name.to_string(),
+      schema,
+    }
+  }
+
+  #[test]
+  fn traces_primitives() {
+    assert_eq!(schema_of::<bool>(), Schema::Boolean);
+    assert_eq!(schema_of::<u32>(), Schema::Number);
+    assert_eq!(schema_of::<f64>(), Schema::Number);
+    assert_eq!(schema_of::<String>(), Schema::String);
+    assert_eq!(schema_of::<()>(), Schema::Null);
+    assert_eq!(schema_of::<serde_json::Value>(), Schema::Any);
+  }
+
+  #[test]
+  fn traces_options_and_sequences() {
+    assert_eq!(
+      schema_of::<Option<String>>(),
+      Schema::Optional {
+        inner: Box::new(Schema::String)
+      }
+    );
+    assert_eq!(
+      schema_of::<Vec<Option<u8>>>(),
+      Schema::Array {
+        items: Box::new(Schema::Optional {
+          inner: Box::new(Schema::Number)
+        })
+      }
+    );
+    assert_eq!(
+      schema_of::<(u32, String)>(),
+      Schema::Tuple {
+        items: vec![Schema::Number, Schema::String]
+      }
+    );
+    assert_eq!(
+      schema_of::<HashMap<String, bool>>(),
+      Schema::Record {
+        values: Box::new(Schema::Boolean)
+      }
+    );
+  }
+
+  #[test]
+  fn traces_structs_and_enums() {
+    assert_eq!(
+      schema_of::<Options>(),
+      Schema::Object {
+        name: "Options".to_string(),
+        fields: vec![
+          field("fileName", Schema::String),
+          field(
+            "size",
+            Schema::Optional {
+              inner: Box::new(Schema::Number)
+            }
+          ),
+          field(
+            "tags",
+            Schema::Array {
+              items: Box::new(Schema::String)
+            }
+          ),
+          field(
+            "mode",
+            Schema::Enum {
+              name: "Mode".to_string(),
+              variants: vec!["read".to_string(), "write".to_string()],
+            }
+          ),
+        ],
+      }
+    );
+  }
+
+  #[test]
+  fn traces_return_markers() {
+    assert_eq!(schema_of::<Binary>(), Schema::Binary);
+    assert_eq!(schema_of::<Stream>(), Schema::Stream);
+    assert_eq!(
+      schema_of::<Subscription<Vec<u32>>>(),
+      Schema::Subscription {
+        message: Box::new(Schema::Array {
+          items: Box::new(Schema::Number)
+        })
+      }
+    );
+  }
+
+  #[test]
+  fn stops_at_recursive_types() {
+    let mut schema = schema_of::<Node>();
+
+    for _ in 0..MAX_DEPTH {
+      schema = match schema {
+        Schema::Object { mut fields, .. } => match fields.remove(0).schema {
+          Schema::Array { items } => *items,
+          schema => schema,
+        },
+        schema => schema,
+      };
+    }
+
+    assert_eq!(schema, Schema::Any);
+  }
+
+  #[test]
+  fn converts_to_typescript() {
+    assert_eq!(
+      schema_of::<Options>().to_typescript(),
+      "{ fileName: string; size?: number | null; tags: string[]; mode: 'read' | 'write'; }"
+    );
+    assert_eq!(
+      schema_of::<Vec<Option<bool>>>().to_typescript(),
+      "(boolean | null)[]"
+    );
+    assert_eq!(
+      schema_of::<Subscription<String>>().to_typescript(),
+      "ChannelRef<string>"
+    );
+  }
+
+  #[test]
+  fn leaves_trailing_optional_args_out() {
+    assert_eq!(
+      Schema::args_to_typescript(&Some(vec![
+        Schema::Optional {
+          inner: Box::new(Schema::String)
+        },
+        Schema::Number,
+        Schema::Any,
+      ])),
+      "[arg0: string | null, arg1: number, arg2?: unknown]"
+    );
+    assert_eq!(Schema::args_to_typescript(&None), "unknown[]");
+  }
+}
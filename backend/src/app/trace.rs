@@ -0,0 +1,354 @@
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{BufRead, BufReader, BufWriter, Write},
+  path::{Path, PathBuf},
+  sync::Mutex,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+  app_paths::traces_dir,
+  invoke::{
+    CancellationToken, InvokeCommand, InvokeError, InvokeResponder, InvokeResult, InvokeResultData,
+  },
+  invoke_args::NO_WINDOW_CODE,
+  window::AppWindowExt,
+  App, AppExt,
+};
+
+/// Replays have no event loop, so invokes waiting on it would never finish.
+const REPLAY_INVOKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Methods that change something outside the app, like files on disk. Replays
+/// reject them unless side effects are allowed, so replaying a trace does not
+/// overwrite files or open dialogs again.
+const SIDE_EFFECT_METHODS: &[&str] = &["fs.write", "dialog.*"];
+const SIDE_EFFECT_CODE: &str = "side_effect";
+
+/// One line of a trace file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TraceLine {
+  /// Unix time in milliseconds.
+  pub ts: u64,
+  /// Milliseconds since the recording started.
+  pub elapsed_ms: u64,
+  #[serde(flatten)]
+  pub entry: TraceEntry,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum TraceEntry {
+  Invoke {
+    /// `None` for invokes issued without a window, like during a replay.
+    window: Option<u32>,
+    request: Option<u64>,
+    method: String,
+    args: Vec<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "base64_blobs")]
+    blobs: Vec<Vec<u8>>,
+  },
+  Result {
+    window: Option<u32>,
+    request: Option<u64>,
+    method: String,
+    result: TraceResult,
+  },
+  Event {
+    name: String,
+    payload: serde_json::Value,
+    target: Vec<u32>,
+  },
+}
+
+/// Blobs are written as base64 strings rather than arrays of numbers.
+mod base64_blobs {
+  use base64::{engine::general_purpose::STANDARD, Engine};
+  use serde::{de, Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(blobs: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(blobs.iter().map(|blob| STANDARD.encode(blob)))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<u8>>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+      .iter()
+      .map(|blob| STANDARD.decode(blob).map_err(de::Error::custom))
+      .collect()
+  }
+}
+
+/// Recorded outcome of an invoke. Binary and stream bodies are not kept.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TraceResult {
+  Json { value: serde_json::Value },
+  Binary { length: usize },
+  Stream { length: Option<u64> },
+  Error { error: InvokeError },
+}
+
+impl From<&InvokeResult> for TraceResult {
+  fn from(result: &InvokeResult) -> Self {
+    match result {
+      InvokeResult::Ok(InvokeResultData::Json(value)) => TraceResult::Json {
+        value: value.clone(),
+      },
+      InvokeResult::Ok(InvokeResultData::Binary(value)) => TraceResult::Binary {
+        length: value.len(),
+      },
+      InvokeResult::Ok(InvokeResultData::Mixed { value, .. }) => TraceResult::Json {
+        value: value.clone(),
+      },
+      InvokeResult::Ok(InvokeResultData::Stream(stream)) => TraceResult::Stream {
+        length: stream.length(),
+      },
+      InvokeResult::Err(err) => TraceResult::Error { error: err.clone() },
+    }
+  }
+}
+
+impl TraceResult {
+  /// Errors are compared by code only, since messages often contain paths or
+  /// other details that change between runs.
+  fn matches(&self, other: &TraceResult) -> bool {
+    match (self, other) {
+      (TraceResult::Error { error: a }, TraceResult::Error { error: b }) => a.code == b.code,
+      (TraceResult::Stream { .. }, TraceResult::Stream { .. }) => true,
+      (a, b) => a == b,
+    }
+  }
+}
+
+/// Writes invokes, their results and dispatched events as JSONL. Enabled by
+/// setting `LENZ_TRACE`; files are written to `app_data()/traces`.
+pub struct TraceRecorder {
+  writer: Mutex<BufWriter<File>>,
+  started_at: Instant,
+  path: PathBuf,
+}
+
+impl TraceRecorder {
+  pub fn from_env() -> Option<Self> {
+    std::env::var("LENZ_TRACE").ok()?;
+
+    match Self::create(&traces_dir()) {
+      Ok(recorder) => {
+        println!("Recording IPC trace to {}", recorder.path.display());
+        Some(recorder)
+      }
+      Err(err) => {
+        eprintln!("Failed to create IPC trace > {}", err);
+        None
+      }
+    }
+  }
+
+  pub fn create(dir: &Path) -> std::io::Result<Self> {
+    std::fs::create_dir_all(dir)?;
+
+    let path = dir.join(format!("trace-{}.jsonl", unix_millis()));
+
+    Ok(Self {
+      writer: Mutex::new(BufWriter::new(File::create(&path)?)),
+      started_at: Instant::now(),
+      path,
+    })
+  }
+
+  pub fn record(&self, entry: TraceEntry) {
+    let line = TraceLine {
+      ts: unix_millis(),
+      elapsed_ms: self.started_at.elapsed().as_millis() as u64,
+      entry,
+    };
+    let mut writer = self.writer.lock().expect("Trace writer lock is poisoned");
+
+    // Flushed on every line so the trace survives a crash.
+    let result = serde_json::to_writer(&mut *writer, &line)
+      .map_err(std::io::Error::from)
+      .and_then(|_| writer.write_all(b"\n"))
+      .and_then(|_| writer.flush());
+
+    if let Err(err) = result {
+      eprintln!("Failed to write IPC trace > {}", err);
+    }
+  }
+}
+
+fn unix_millis() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// Records the invoke and wraps the responder so its result is recorded too.
+pub fn trace_invoke(
+  app: &App,
+  command: &InvokeCommand,
+  request: Option<u64>,
+  responder: InvokeResponder,
+) -> InvokeResponder {
+  let trace = match &app.trace {
+    Some(trace) => trace,
+    None => return responder,
+  };
+  let window = command.window.as_ref().map(|window| window.id());
+  let method = command.method.clone();
+
+  trace.record(TraceEntry::Invoke {
+    window,
+    request,
+    method: method.clone(),
+    args: command.args.clone(),
+    blobs: command.blobs.clone(),
+  });
+
+  let app = app.clone();
+
+  responder.map(move |result| {
+    if let Some(trace) = &app.trace {
+      trace.record(TraceEntry::Result {
+        window,
+        request,
+        method,
+        result: TraceResult::from(&result),
+      });
+    }
+
+    result
+  })
+}
+
+#[derive(Debug, Default)]
+pub struct ReplaySummary {
+  pub invokes: usize,
+  pub mismatches: usize,
+  /// Invokes of methods that need the calling window, or that have side
+  /// effects when those are not allowed.
+  pub skipped: usize,
+}
+
+/// Re-issues the invokes of a trace, one at a time and in recorded order,
+/// and compares each result with the recorded one.
+///
+/// Replays are headless: no window exists, so the recorded window ids refer to
+/// nothing and are only used to pair invokes with their results. Invokes are
+/// issued without a calling window, and those of methods that need one are
+/// skipped instead of compared. So are methods with side effects, like
+/// `fs.write`, unless `allow_side_effects` is set; they are rejected also when
+/// called from a batch.
+pub async fn replay(
+  app: App,
+  path: &Path,
+  allow_side_effects: bool,
+) -> std::io::Result<ReplaySummary> {
+  if !allow_side_effects {
+    for pattern in SIDE_EFFECT_METHODS {
+      app.add_invoke_middleware(pattern, |command, responder, _| {
+        responder.reply(
+          InvokeError::new(
+            SIDE_EFFECT_CODE,
+            format!("{} has side effects and is not replayed", command.method),
+          )
+          .into(),
+        );
+      });
+    }
+  }
+
+  let mut invokes = Vec::new();
+  let mut results = HashMap::new();
+
+  for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+    let line = line?;
+
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let line: TraceLine = serde_json::from_str(&line).map_err(|err| {
+      std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Invalid trace line {}: {}", index + 1, err),
+      )
+    })?;
+
+    match line.entry {
+      TraceEntry::Invoke {
+        window,
+        request,
+        method,
+        args,
+        blobs,
+      } => invokes.push((window, request, method, args, blobs)),
+      TraceEntry::Result {
+        window,
+        request: Some(request),
+        result,
+        ..
+      } => {
+        results.insert((window, request), result);
+      }
+      _ => {}
+    }
+  }
+
+  let mut summary = ReplaySummary::default();
+
+  for (window_id, request, method, args, blobs) in invokes {
+    let (responder, receiver) = InvokeResponder::channel(app.clone());
+
+    app.invoke(
+      InvokeCommand {
+        app: app.clone(),
+        method: method.clone(),
+        args,
+        blobs,
+        window: None,
+        cancellation: CancellationToken::new(),
+      },
+      responder,
+    );
+
+    let result = match tokio::time::timeout(REPLAY_INVOKE_TIMEOUT, receiver).await {
+      Ok(Ok(result)) => result,
+      Ok(Err(_)) => InvokeError::new("internal", "Handler dropped the request").into(),
+      Err(_) => InvokeError::timeout(&method, REPLAY_INVOKE_TIMEOUT).into(),
+    };
+    let result = TraceResult::from(&result);
+    let recorded = request.and_then(|request| results.get(&(window_id, request)));
+
+    summary.invokes += 1;
+
+    if matches!(&result, TraceResult::Error { error } if error.code == NO_WINDOW_CODE) {
+      summary.skipped += 1;
+      println!("[replay] {} -> skipped, needs a window", method);
+      continue;
+    }
+
+    if matches!(&result, TraceResult::Error { error } if error.code == SIDE_EFFECT_CODE) {
+      summary.skipped += 1;
+      println!("[replay] {} -> skipped, has side effects", method);
+      continue;
+    }
+
+    match recorded {
+      Some(recorded) if !recorded.matches(&result) => {
+        summary.mismatches += 1;
+        println!(
+          "[replay] {} -> mismatch\n  recorded: {}\n  replayed: {}",
+          method,
+          serde_json::to_string(recorded).unwrap_or_default(),
+          serde_json::to_string(&result).unwrap_or_default()
+        );
+      }
+      Some(_) => println!("[replay] {} -> ok", method),
+      None => println!("[replay] {} -> no recorded result", method),
+    }
+  }
+
+  Ok(summary)
+}
@@ -0,0 +1,102 @@
+use std::{fmt::Write, path::Path};
+
+use super::{
+  schema::{describe_invoke_methods, Schema},
+  App,
+};
+
+pub const TYPINGS_FILE_NAME: &str = "lenz.d.ts";
+
+const IPC_DECLARATIONS: &str = r#"
+  export type InvokeMethod = keyof InvokeMethods;
+  export type InvokeArgs<M extends InvokeMethod> = InvokeMethods[M]['args'];
+  export type InvokeReturns<M extends InvokeMethod> = InvokeMethods[M]['returns'];
+  export type ChannelRef<T> = { $channel: number; readonly __message?: T };
+  export type SubscriptionMethod = {
+    [M in InvokeMethod]: InvokeReturns<M> extends ChannelRef<any> ? M : never;
+  }[InvokeMethod];
+  export type SubscriptionMessage<M extends InvokeMethod> =
+    InvokeReturns<M> extends ChannelRef<infer T> ? T : never;
+
+  export class InvokeError extends Error {
+    constructor(error: unknown);
+    code: string;
+    data: unknown;
+  }
+
+  export class InvokeStream extends ReadableStream<Uint8Array> {
+    constructor(id: number, length?: number | null);
+    id: number;
+    length: number | null;
+    onprogress: ((progress: { loaded: number; length: number | null }) => void) | null;
+  }
+
+  export class Subscription<T = unknown> {
+    constructor(id: number, callback: (message: T) => void);
+    id: number;
+    callback: (message: T) => void;
+    onclose: (() => void) | null;
+    unsubscribe(): Promise<boolean>;
+  }
+
+  export type BatchCall = { method: string; args?: unknown[] };
+  export type BatchResult = { ok: true; value: unknown } | { ok: false; error: InvokeError };
+
+  export function getWindowId(): number | undefined;
+  export function on(event: string, callback: (payload: any) => void): () => void;
+  export function off(event: string, callback: (payload: any) => void): void;
+  export function handle(method: string, callback: (...args: any[]) => unknown): () => void;
+
+  export function invokeAsync<M extends InvokeMethod>(name: M, ...params: InvokeArgs<M>): Promise<InvokeReturns<M>>;
+  export function invokeAsync<M extends InvokeMethod>(name: M, ...params: [...InvokeArgs<M>, AbortSignal]): Promise<InvokeReturns<M>>;
+  export function invokeAsync<T = unknown>(name: string, ...params: unknown[]): Promise<T>;
+
+  export function invokeSync<M extends InvokeMethod>(name: M, ...params: InvokeArgs<M>): InvokeReturns<M>;
+  export function invokeSync<T = unknown>(name: string, ...params: unknown[]): T;
+
+  export function invokeBatch(calls: BatchCall[], options?: { mode?: 'sequential' | 'parallel'; signal?: AbortSignal }): Promise<BatchResult[]>;
+
+  export function subscribe<M extends SubscriptionMethod>(name: M, ...params: [...InvokeArgs<M>, (message: SubscriptionMessage<M>) => void]): Promise<Subscription<SubscriptionMessage<M>>>;
+  export function subscribe<T = unknown>(name: string, ...params: unknown[]): Promise<Subscription<T>>;
+"#;
+
+/// Declarations for `lenz/ipc`, with argument and return types of every
+/// method registered at the time of the call.
+pub fn generate_typings(app: &App) -> String {
+  let mut output = String::new();
+
+  writeln!(
+    output,
+    "// Generated by `lenz --emit-typings`. Do not edit."
+  )
+  .unwrap();
+  writeln!(output).unwrap();
+  writeln!(output, "declare module 'lenz/ipc' {{").unwrap();
+  writeln!(output, "  export interface InvokeMethods {{").unwrap();
+
+  for method in describe_invoke_methods(app) {
+    writeln!(
+      output,
+      "    {}: {{ args: {}; returns: {} }};",
+      serde_json::to_string(&method.name).unwrap(),
+      Schema::args_to_typescript(&method.description.args),
+      method.description.returns.to_typescript()
+    )
+    .unwrap();
+  }
+
+  writeln!(output, "  }}").unwrap();
+  output.push_str(IPC_DECLARATIONS);
+  writeln!(output, "}}").unwrap();
+
+  output
+}
+
+pub fn write_typings(app: &App, dir: &Path) -> std::io::Result<std::path::PathBuf> {
+  let path = dir.join(TYPINGS_FILE_NAME);
+
+  std::fs::create_dir_all(dir)?;
+  std::fs::write(&path, generate_typings(app))?;
+
+  Ok(path)
+}
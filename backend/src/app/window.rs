@@ -1,20 +1,44 @@
 use std::{
-  collections::{HashMap, HashSet},
-  sync::{Arc, RwLock},
+  collections::{HashMap, HashSet, VecDeque},
+  future::Future,
+  sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Mutex, RwLock,
+  },
+  time::Duration,
 };
 
+use serde::Deserialize;
 use tao::{
-  dpi::PhysicalSize,
+  dpi::{PhysicalPosition, PhysicalSize},
+  error::ExternalError,
   event::Event,
-  event_loop::EventLoop,
+  event_loop::EventLoopWindowTarget,
+  monitor::MonitorHandle,
   rwh_06::{HasWindowHandle, RawWindowHandle},
-  window::WindowId,
+  window::{Fullscreen, ResizeDirection, WindowId},
 };
 use wry::{http::Request, RequestAsyncResponder, WebContext};
 
+use serde_json::json;
+use tokio::sync::oneshot;
+
 use crate::app::{App, AppExt};
 
-use super::app_paths::app_data;
+use super::{
+  app_paths::app_data,
+  invoke::InvokeError,
+  menu, monitors,
+  window_events::{self, WindowStateFlags},
+  window_state,
+};
+
+const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_PENDING_EVENTS: usize = 1024;
+
+static NEXT_CALL_ID: AtomicU32 = AtomicU32::new(1);
+
+pub type CallResult = Result<serde_json::Value, InvokeError>;
 
 #[derive(Debug)]
 pub enum AppWindowEvent {
@@ -26,6 +50,48 @@ pub enum AppWindowEvent {
   Close {
     window_id: u32,
   },
+  /// Builds a window on the event loop thread and replies with its id, or
+  /// why it couldn't be built.
+  CreateWindow {
+    options: WindowOptions,
+    reply: oneshot::Sender<Result<u32, String>>,
+  },
+  /// Menu bar models or active extensions changed.
+  UpdateMenus,
+  /// Shows a context menu in the window, see `menu::popup`.
+  Popup {
+    window_id: u32,
+    items: Vec<menu::PopupMenuItem>,
+    position: Option<menu::PopupPosition>,
+    reply: oneshot::Sender<Option<String>>,
+  },
+  /// A context menu was dismissed.
+  ClosePopup {
+    window_id: u32,
+    popup_id: u32,
+  },
+  /// The window started loading a page, replacing the previous one.
+  PageLoadStarted {
+    window_id: u32,
+  },
+  /// Starts moving the window with the pressed mouse button, or resizing it
+  /// from `direction`, on the event loop thread.
+  Drag {
+    window_id: u32,
+    direction: Option<ResizeDirection>,
+    reply: oneshot::Sender<Result<(), String>>,
+  },
+  /// Runs a task on the event loop thread, see `run_on_main_thread`.
+  RunOnMainThread {
+    task: MainThreadTask,
+  },
+  /// The page of the window imported `lenz/ipc` and can receive events.
+  /// Ignored when `generation` is not the one of the current page, i.e. when
+  /// it was sent by a page that has since been replaced.
+  PageReady {
+    window_id: u32,
+    generation: u64,
+  },
   Unknown,
   Ready,
 }
@@ -45,6 +111,82 @@ impl From<Event<'_, AppWindowEvent>> for AppWindowEvent {
   }
 }
 
+/// Options of a window created with the `window.create` invoke. Sizes and
+/// positions are physical pixels; windows are centered unless `x` and `y` are set.
+/// `parent` is resolved when the window is built, with `AppWindowExt::build_window`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WindowOptions {
+  pub url: Option<String>,
+  pub label: Option<String>,
+  pub title: Option<String>,
+  pub width: Option<f64>,
+  pub height: Option<f64>,
+  pub x: Option<i32>,
+  pub y: Option<i32>,
+  pub decorations: Option<bool>,
+  pub resizable: Option<bool>,
+  pub transparent: bool,
+  pub visible: Option<bool>,
+  pub parent: Option<u32>,
+  pub modal: bool,
+  pub devtools: bool,
+  /// Key to save and restore the window geometry under.
+  pub restore_state: Option<String>,
+  /// Id of the menu bar to show, `"main"` for the default one.
+  pub menu_bar: Option<String>,
+}
+
+/// A closure sent to the event loop thread by `run_on_main_thread`.
+pub struct MainThreadTask(Box<dyn FnOnce() + Send + 'static>);
+
+impl MainThreadTask {
+  pub fn run(self) {
+    (self.0)()
+  }
+}
+
+impl std::fmt::Debug for MainThreadTask {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("MainThreadTask")
+  }
+}
+
+/// Runs `task` on the event loop thread and resolves with what it returns, or
+/// `None` when the event loop is gone. Monitor queries and geometry changes
+/// go through here, as some platforms (GDK on Linux) only allow them on the
+/// thread running the event loop.
+pub async fn run_on_main_thread<R: Send + 'static>(
+  app: &App,
+  task: impl FnOnce() -> R + Send + 'static,
+) -> Option<R> {
+  let (reply, result) = oneshot::channel();
+
+  app
+    .event_loop_proxy
+    .send_event(AppWindowEvent::RunOnMainThread {
+      task: MainThreadTask(Box::new(move || {
+        let _ = reply.send(task());
+      })),
+    })
+    .ok()?;
+
+  result.await.ok()
+}
+
+/// Events dispatched before the page is ready are held here and flushed in
+/// order once it is. Loading a new page, including reloads, re-arms it and
+/// bumps `generation`.
+#[derive(Default)]
+pub struct EventQueue {
+  ready: bool,
+  generation: u64,
+  /// Whether events were dropped since the page started loading, so it's
+  /// only reported once for pages that never import `lenz/ipc`.
+  overflowed: bool,
+  pending: VecDeque<String>,
+}
+
 pub struct ApplicationWindow {
   receiver: std::sync::mpsc::Receiver<AppWindowEvent>,
   pub labels: RwLock<HashSet<String>>,
@@ -52,6 +194,17 @@ pub struct ApplicationWindow {
   pub tao_window: Arc<tao::window::Window>,
   pub wry_webview: wry::WebView,
   pub import_map: RwLock<HashMap<String, String>>,
+  pub pending_calls: Mutex<HashMap<u32, oneshot::Sender<CallResult>>>,
+  pub event_queue: Arc<Mutex<EventQueue>>,
+  pub state_flags: Mutex<WindowStateFlags>,
+  /// Key the window geometry is saved under when it closes.
+  pub restore_state: Option<String>,
+  /// Window this one stays on top of and closes with.
+  pub parent_id: Option<u32>,
+  /// Blocks input to the parent while open.
+  pub modal: bool,
+  /// Id of the menu bar model the window shows.
+  pub menu_bar: Option<String>,
 }
 
 unsafe impl Send for ApplicationWindow {}
@@ -87,10 +240,55 @@ pub trait AppWindowExt {
   fn hide(&self);
   fn eval(&self, script: &str);
   fn emit(&self, event: &str, payload: serde_json::Value);
+  fn dispatch(&self, event: &str, payload: &serde_json::Value);
+  fn set_page_ready(&self, generation: u64);
+  fn is_page_ready(&self) -> bool;
+  fn page_generation(&self) -> u64;
+  fn call(
+    &self,
+    method: &str,
+    args: Vec<serde_json::Value>,
+  ) -> impl Future<Output = CallResult> + Send;
+  fn call_with_timeout(
+    &self,
+    method: &str,
+    args: Vec<serde_json::Value>,
+    timeout: Duration,
+  ) -> impl Future<Output = CallResult> + Send;
+  fn resolve_call(&self, id: u32, result: CallResult);
+  fn cancel_calls(&self, reason: &str);
   fn app(&self) -> App;
   fn has_label(&self, label: &str) -> bool;
   fn window_handle(&self) -> WindowHandle;
   fn center(&self);
+  fn center_on(&self, monitor: &MonitorHandle);
+  fn current_monitor(&self) -> Option<MonitorHandle>;
+  fn move_to_monitor(&self, monitor: &MonitorHandle);
+  fn size(&self) -> PhysicalSize<u32>;
+  fn set_size(&self, width: u32, height: u32);
+  fn position(&self) -> PhysicalPosition<i32>;
+  fn set_position(&self, x: i32, y: i32);
+  fn set_min_size(&self, size: Option<PhysicalSize<u32>>);
+  fn set_max_size(&self, size: Option<PhysicalSize<u32>>);
+  fn is_maximized(&self) -> bool;
+  fn set_maximized(&self, maximized: bool);
+  fn is_minimized(&self) -> bool;
+  fn set_minimized(&self, minimized: bool);
+  fn is_fullscreen(&self) -> bool;
+  fn set_fullscreen(&self, fullscreen: bool);
+  fn set_always_on_top(&self, always_on_top: bool);
+  fn is_resizable(&self) -> bool;
+  fn set_resizable(&self, resizable: bool);
+  fn is_decorated(&self) -> bool;
+  fn set_decorations(&self, decorations: bool);
+  fn is_visible(&self) -> bool;
+  fn is_focused(&self) -> bool;
+  fn focus(&self);
+  fn parent(&self) -> Option<AppWindow>;
+  fn children(&self) -> Vec<AppWindow>;
+  fn set_input_enabled(&self, enabled: bool);
+  fn drag(&self) -> Result<(), ExternalError>;
+  fn drag_resize(&self, direction: ResizeDirection) -> Result<(), ExternalError>;
   fn block_until_ready(&self);
   fn build_window(&self) -> AppWindowBuilder;
 }
@@ -112,18 +310,14 @@ impl AppWindowExt for AppWindow {
     self.tao_window.title()
   }
 
+  /// Closes the window without asking the page. Windows are dropped on the
+  /// event loop, which also exits once the last one is gone.
   fn close(&self) {
     self.hide();
-    let app = self.app();
-
-    let id = self.id();
-    let mut windows = app
-      .windows
-      .write()
-      .expect("Failed to acquire lock on windows")
-      .clone();
 
-    windows.remove(&id);
+    let _ = self.app.event_loop_proxy.send_event(AppWindowEvent::Close {
+      window_id: self.id(),
+    });
   }
 
   fn window_handle(&self) -> WindowHandle {
@@ -153,22 +347,164 @@ impl AppWindowExt for AppWindow {
   }
 
   fn eval(&self, script: &str) {
-    self
-      .wry_webview
-      .evaluate_script(script)
-      .expect("Failed to evaluate script");
+    if let Err(err) = self.wry_webview.evaluate_script(script) {
+      eprintln!(
+        "Failed to evaluate script in window {} > {}",
+        self.id(),
+        err
+      );
+    }
+  }
+
+  /// Calls `window.__dispatch` in the page, or queues the event until the page
+  /// is ready. Must be called from the event loop thread.
+  fn dispatch(&self, event: &str, payload: &serde_json::Value) {
+    let script = format!(
+      "window.__dispatch({}, {});",
+      serde_json::to_string(event).unwrap(),
+      serde_json::to_string(payload).unwrap()
+    );
+    let mut queue = self
+      .event_queue
+      .lock()
+      .expect("Event queue lock is poisoned");
+
+    if queue.ready {
+      drop(queue);
+      return self.eval(&script);
+    }
+
+    if queue.pending.len() >= MAX_PENDING_EVENTS {
+      if !queue.overflowed {
+        queue.overflowed = true;
+        eprintln!(
+          "Dropping events queued for window {}: page never became ready",
+          self.id()
+        );
+      }
+      queue.pending.pop_front();
+    }
+
+    queue.pending.push_back(script);
+  }
+
+  /// Must be called from the event loop thread. Does nothing when the page
+  /// of `generation` has been replaced.
+  fn set_page_ready(&self, generation: u64) {
+    let pending: Vec<String> = {
+      let mut queue = self
+        .event_queue
+        .lock()
+        .expect("Event queue lock is poisoned");
+
+      if queue.generation != generation {
+        return;
+      }
+
+      queue.ready = true;
+      queue.pending.drain(..).collect()
+    };
+
+    for script in pending {
+      self.eval(&script);
+    }
   }
 
   fn emit(&self, event: &str, payload: serde_json::Value) {
+    self.app.emit_to(event, payload, vec![self.id()]);
+  }
+
+  fn is_page_ready(&self) -> bool {
     self
-      .app
-      .event_loop_proxy
-      .send_event(AppWindowEvent::Event {
-        name: event.to_string(),
-        payload,
-        target: vec![self.id()],
-      })
-      .expect("Failed to send event");
+      .event_queue
+      .lock()
+      .expect("Event queue lock is poisoned")
+      .ready
+  }
+
+  fn page_generation(&self) -> u64 {
+    self
+      .event_queue
+      .lock()
+      .expect("Event queue lock is poisoned")
+      .generation
+  }
+
+  fn call(
+    &self,
+    method: &str,
+    args: Vec<serde_json::Value>,
+  ) -> impl Future<Output = CallResult> + Send {
+    self.call_with_timeout(method, args, DEFAULT_CALL_TIMEOUT)
+  }
+
+  /// Invokes a handler registered with `handle` from `lenz/ipc` in this
+  /// window and resolves with the value it returns.
+  fn call_with_timeout(
+    &self,
+    method: &str,
+    args: Vec<serde_json::Value>,
+    timeout: Duration,
+  ) -> impl Future<Output = CallResult> + Send {
+    let id = NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed);
+    let (sender, receiver) = oneshot::channel();
+
+    self
+      .pending_calls
+      .lock()
+      .expect("Pending calls lock is poisoned")
+      .insert(id, sender);
+
+    self.emit(
+      "ipc.call",
+      json!({ "id": id, "method": method, "args": args }),
+    );
+
+    let window = self.clone();
+    let method = method.to_string();
+
+    async move {
+      match tokio::time::timeout(timeout, receiver).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => Err(InvokeError::new("cancelled", "Window was closed")),
+        Err(_) => {
+          window
+            .pending_calls
+            .lock()
+            .expect("Pending calls lock is poisoned")
+            .remove(&id);
+
+          Err(InvokeError::timeout(&method, timeout))
+        }
+      }
+    }
+  }
+
+  fn resolve_call(&self, id: u32, result: CallResult) {
+    let sender = self
+      .pending_calls
+      .lock()
+      .expect("Pending calls lock is poisoned")
+      .remove(&id);
+
+    if let Some(sender) = sender {
+      let _ = sender.send(result);
+    }
+  }
+
+  /// Fails every call still waiting for the page, which will never answer
+  /// once it is closed or reloaded.
+  fn cancel_calls(&self, reason: &str) {
+    let senders = std::mem::take(
+      &mut *self
+        .pending_calls
+        .lock()
+        .expect("Pending calls lock is poisoned"),
+    );
+
+    for sender in senders.into_values() {
+      let _ = sender.send(Err(InvokeError::new("cancelled", reason)));
+    }
   }
 
   fn app(&self) -> App {
@@ -183,23 +519,222 @@ impl AppWindowExt for AppWindow {
       .contains(label)
   }
 
+  /// Centers the window on the monitor it is on.
   fn center(&self) {
-    let tao_window = self.tao_window.clone();
+    if let Some(monitor) = self.current_monitor() {
+      self.center_on(&monitor);
+    }
+  }
 
-    let PhysicalSize { width, height } = tao_window.inner_size();
+  /// Must be called from the event loop thread, like the methods below.
+  fn center_on(&self, monitor: &MonitorHandle) {
+    self
+      .tao_window
+      .set_outer_position(monitors::centered_position(
+        monitor,
+        self.tao_window.outer_size(),
+      ));
+  }
 
-    let PhysicalSize {
-      width: screen_width,
-      height: screen_height,
-    } = tao_window
+  /// The monitor the window is on, or the primary one while it isn't mapped.
+  fn current_monitor(&self) -> Option<MonitorHandle> {
+    self
+      .tao_window
       .current_monitor()
-      .expect("Failed to get current monitor")
-      .size();
+      .or_else(|| self.tao_window.primary_monitor())
+  }
+
+  /// Moves the window to the same spot on another monitor, keeping it
+  /// maximized or fullscreen. The system rescales the window when the
+  /// monitors have different scale factors, so it is kept on the target
+  /// monitor at its rescaled size.
+  fn move_to_monitor(&self, monitor: &MonitorHandle) {
+    let maximized = self.is_maximized();
+    let fullscreen = self.is_fullscreen();
+
+    if maximized {
+      self.set_maximized(false);
+    }
+
+    let current = self.current_monitor();
+    let scale = current.as_ref().map_or(1.0, |current| {
+      monitor.scale_factor() / current.scale_factor()
+    });
+    let offset = match &current {
+      Some(current) => {
+        let origin = monitors::work_area(current);
+        let position = self.position();
+
+        PhysicalPosition::new(
+          ((position.x - origin.x) as f64 * scale) as i32,
+          ((position.y - origin.y) as f64 * scale) as i32,
+        )
+      }
+      None => PhysicalPosition::new(0, 0),
+    };
+    let size = self.tao_window.outer_size();
+    let size = PhysicalSize::new(
+      (size.width as f64 * scale).round() as u32,
+      (size.height as f64 * scale).round() as u32,
+    );
+    let origin = monitors::work_area(monitor);
+
+    self
+      .tao_window
+      .set_outer_position(monitors::clamped_position(
+        monitor,
+        PhysicalPosition::new(origin.x + offset.x, origin.y + offset.y),
+        size,
+      ));
+
+    if fullscreen {
+      self
+        .tao_window
+        .set_fullscreen(Some(Fullscreen::Borderless(Some(monitor.clone()))));
+    }
+
+    if maximized {
+      self.set_maximized(true);
+    }
+  }
+
+  /// Inner size in physical pixels.
+  fn size(&self) -> PhysicalSize<u32> {
+    self.tao_window.inner_size()
+  }
+
+  fn set_size(&self, width: u32, height: u32) {
+    self
+      .tao_window
+      .set_inner_size(PhysicalSize::new(width, height));
+  }
+
+  /// Outer position in physical pixels, or the origin where the platform
+  /// can't tell, such as Wayland.
+  fn position(&self) -> PhysicalPosition<i32> {
+    self.tao_window.outer_position().unwrap_or_default()
+  }
+
+  fn set_position(&self, x: i32, y: i32) {
+    self
+      .tao_window
+      .set_outer_position(PhysicalPosition::new(x, y));
+  }
+
+  fn set_min_size(&self, size: Option<PhysicalSize<u32>>) {
+    self.tao_window.set_min_inner_size(size);
+  }
+
+  fn set_max_size(&self, size: Option<PhysicalSize<u32>>) {
+    self.tao_window.set_max_inner_size(size);
+  }
+
+  fn is_maximized(&self) -> bool {
+    self.tao_window.is_maximized()
+  }
+
+  fn set_maximized(&self, maximized: bool) {
+    self.tao_window.set_maximized(maximized);
+  }
+
+  fn is_minimized(&self) -> bool {
+    self.tao_window.is_minimized()
+  }
+
+  fn set_minimized(&self, minimized: bool) {
+    self.tao_window.set_minimized(minimized);
+  }
 
-    let x = (screen_width - width) / 2;
-    let y = (screen_height - height) / 2;
+  fn is_fullscreen(&self) -> bool {
+    self.tao_window.fullscreen().is_some()
+  }
+
+  /// Borderless fullscreen on the monitor the window is on.
+  fn set_fullscreen(&self, fullscreen: bool) {
+    self
+      .tao_window
+      .set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+  }
+
+  fn set_always_on_top(&self, always_on_top: bool) {
+    self.tao_window.set_always_on_top(always_on_top);
+  }
+
+  fn is_resizable(&self) -> bool {
+    self.tao_window.is_resizable()
+  }
 
-    tao_window.set_outer_position(tao::dpi::PhysicalPosition::new(x, y));
+  fn set_resizable(&self, resizable: bool) {
+    self.tao_window.set_resizable(resizable);
+    window_events::emit_frame_changed(self);
+  }
+
+  fn is_decorated(&self) -> bool {
+    self.tao_window.is_decorated()
+  }
+
+  fn set_decorations(&self, decorations: bool) {
+    self.tao_window.set_decorations(decorations);
+    window_events::emit_frame_changed(self);
+  }
+
+  fn is_visible(&self) -> bool {
+    self.tao_window.is_visible()
+  }
+
+  fn is_focused(&self) -> bool {
+    self.tao_window.is_focused()
+  }
+
+  fn focus(&self) {
+    self.tao_window.set_focus();
+  }
+
+  fn parent(&self) -> Option<AppWindow> {
+    self.parent_id.and_then(|id| self.app.get_window(id))
+  }
+
+  fn children(&self) -> Vec<AppWindow> {
+    let id = self.id();
+
+    self
+      .app
+      .windows
+      .read()
+      .expect("Failed to acquire lock on windows")
+      .values()
+      .filter(|window| window.parent_id == Some(id))
+      .cloned()
+      .collect()
+  }
+
+  /// Blocks or restores input to the window while a modal child is open. The
+  /// page is made inert, and Windows also disables the native window. Pages
+  /// loaded meanwhile are made inert again once ready.
+  fn set_input_enabled(&self, enabled: bool) {
+    #[cfg(target_os = "windows")]
+    {
+      use tao::platform::windows::WindowExtWindows;
+      self.tao_window.set_enable(enabled);
+    }
+
+    self.eval(&format!("document.documentElement.inert = {};", !enabled));
+  }
+
+  /// Moves the window with the mouse while the pressed button is held.
+  fn drag(&self) -> Result<(), ExternalError> {
+    self.tao_window.drag_window()
+  }
+
+  /// Resizes the window from an edge while the pressed button is held.
+  /// Maximized windows keep their size. Must be called from the event loop
+  /// thread, like `drag`.
+  fn drag_resize(&self, direction: ResizeDirection) -> Result<(), ExternalError> {
+    if self.is_maximized() {
+      return Ok(());
+    }
+
+    self.tao_window.drag_resize_window(direction)
   }
 
   fn build_window(&self) -> AppWindowBuilder {
@@ -220,6 +755,9 @@ pub struct AppWindowBuilder {
   devtools: bool,
   transparent: bool,
   at_center: bool,
+  restore_state: Option<String>,
+  modal: bool,
+  menu_bar: Option<String>,
   pub import_map: HashMap<String, String>,
 }
 
@@ -238,6 +776,9 @@ impl AppWindowBuilder {
       labels: HashSet::new(),
       devtools: false,
       at_center: false,
+      restore_state: None,
+      modal: false,
+      menu_bar: None,
       transparent: false,
     }
   }
@@ -248,6 +789,26 @@ impl AppWindowBuilder {
     self
   }
 
+  /// Shows the default menu bar, built from the model set with
+  /// `AppExt::set_menu_bar` and the commands placed by extensions.
+  pub fn with_menu_bar(self) -> Self {
+    self.with_menu_bar_id(menu::DEFAULT_MENU_BAR)
+  }
+
+  pub fn with_menu_bar_id(mut self, id: impl Into<String>) -> Self {
+    self.menu_bar = Some(id.into());
+
+    self
+  }
+
+  /// Blocks input to the parent window until this one closes. Has no effect
+  /// without a parent.
+  pub fn with_modal(mut self, modal: bool) -> Self {
+    self.modal = modal;
+
+    self
+  }
+
   pub fn with_label(mut self, label: impl Into<String>) -> Self {
     self.labels.insert(label.into());
 
@@ -317,6 +878,12 @@ impl AppWindowBuilder {
     self
   }
 
+  pub fn with_resizable(mut self, resizable: bool) -> Self {
+    self.tao_window_builder = self.tao_window_builder.with_resizable(resizable);
+
+    self
+  }
+
   pub fn with_size(mut self, width: f64, height: f64) -> Self {
     self.tao_window_builder = self
       .tao_window_builder
@@ -325,6 +892,67 @@ impl AppWindowBuilder {
     self
   }
 
+  pub fn with_position(mut self, x: i32, y: i32) -> Self {
+    self.tao_window_builder = self
+      .tao_window_builder
+      .with_position(PhysicalPosition::new(x, y));
+
+    self
+  }
+
+  pub fn with_options(mut self, options: WindowOptions) -> Self {
+    if let Some(label) = options.label {
+      self = self.with_label(label);
+    }
+
+    if let Some(title) = options.title {
+      self = self.with_title(title);
+    }
+
+    if let Some(url) = options.url {
+      self = self.with_url(url);
+    }
+
+    if let (Some(width), Some(height)) = (options.width, options.height) {
+      self = self.with_size(width, height);
+    }
+
+    self = match (options.x, options.y) {
+      (Some(x), Some(y)) => self.with_position(x, y),
+      _ => self.at_center(),
+    };
+
+    if options.modal {
+      self = self.with_modal(true);
+    }
+
+    if options.devtools {
+      self = self.with_devtools();
+    }
+
+    if let Some(key) = options.restore_state {
+      self = self.with_restore_state(key);
+    }
+
+    if let Some(id) = options.menu_bar {
+      self = self.with_menu_bar_id(id);
+    }
+
+    self
+      .with_decorations(options.decorations.unwrap_or(true))
+      .with_resizable(options.resizable.unwrap_or(true))
+      .with_transparent(options.transparent)
+      .with_visible(options.visible.unwrap_or(true))
+  }
+
+  /// Restores the size, position and maximized state saved under `key` by a
+  /// previous session, and saves them again when the window closes.
+  pub fn with_restore_state(mut self, key: impl Into<String>) -> Self {
+    self.restore_state = Some(key.into());
+
+    self
+  }
+
   pub fn at_center(mut self) -> Self {
     self.at_center = true;
 
@@ -337,14 +965,30 @@ impl AppWindowBuilder {
     self
   }
 
-  pub fn build(self, event_loop: &EventLoop<AppWindowEvent>) -> AppWindow {
+  pub fn build(self, event_loop: &EventLoopWindowTarget<AppWindowEvent>) -> AppWindow {
+    self.try_build(event_loop).expect("Failed to build window")
+  }
+
+  /// Like `build`, but fails instead of panicking when the platform can't
+  /// create the window or its webview.
+  pub fn try_build(
+    self,
+    event_loop: &EventLoopWindowTarget<AppWindowEvent>,
+  ) -> Result<AppWindow, String> {
     let (tx, rx) = std::sync::mpsc::channel();
+    let mut tao_window_builder = self.tao_window_builder.with_transparent(self.transparent);
+
+    // Modal windows become sheets on macOS, which attaches them instead.
+    if let Some(parent) = &self.parent {
+      if !(cfg!(target_os = "macos") && self.modal) {
+        tao_window_builder = with_owner(tao_window_builder, parent);
+      }
+    }
+
     let tao_window = Arc::new(
-      self
-        .tao_window_builder
-        .with_transparent(self.transparent)
+      tao_window_builder
         .build(event_loop)
-        .expect("Failed to build window"),
+        .map_err(|err| format!("Failed to build window > {}", err))?,
     );
 
     #[cfg(any(
@@ -420,8 +1064,25 @@ impl AppWindowBuilder {
           }),
       );
 
-    builder = builder.with_on_page_load_handler(move |event, _| {
-      if let wry::PageLoadEvent::Finished = event {
+    let event_queue = Arc::new(Mutex::new(EventQueue::default()));
+    let page_event_queue = event_queue.clone();
+    let event_loop_proxy = self.app.event_loop_proxy.clone();
+    let window_id = ApplicationWindow::window_id_to_u32(tao_window.id());
+
+    builder = builder.with_on_page_load_handler(move |event, _| match event {
+      wry::PageLoadEvent::Started => {
+        let mut queue = page_event_queue
+          .lock()
+          .expect("Event queue lock is poisoned");
+
+        queue.ready = false;
+        queue.generation += 1;
+        queue.overflowed = false;
+        drop(queue);
+
+        let _ = event_loop_proxy.send_event(AppWindowEvent::PageLoadStarted { window_id });
+      }
+      wry::PageLoadEvent::Finished => {
         tx.send(AppWindowEvent::Ready)
           .expect("Failed to send ready event");
       }
@@ -439,7 +1100,9 @@ impl AppWindowBuilder {
       builder = builder.with_devtools(self.devtools);
     }
 
-    let wry_webview = builder.build().expect("Failed to build webview");
+    let wry_webview = builder
+      .build()
+      .map_err(|err| format!("Failed to build webview > {}", err))?;
 
     #[cfg(debug_assertions)]
     {
@@ -455,10 +1118,33 @@ impl AppWindowBuilder {
       import_map: RwLock::new(self.import_map),
       labels: RwLock::new(self.labels),
       receiver: rx,
+      pending_calls: Mutex::new(HashMap::new()),
+      event_queue,
+      state_flags: Mutex::new(WindowStateFlags::default()),
+      restore_state: self.restore_state,
+      parent_id: self.parent.as_ref().map(|parent| parent.id()),
+      modal: self.modal && self.parent.is_some(),
+      menu_bar: self.menu_bar,
     });
 
-    if self.at_center {
-      window.center();
+    menu::install(&window);
+
+    let restored = window
+      .restore_state
+      .as_deref()
+      .and_then(window_state::load)
+      .is_some_and(|geometry| window_state::restore(&tao_window, &geometry));
+
+    // Child windows open on the monitor of their parent.
+    if self.at_center && !restored {
+      match self
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.current_monitor())
+      {
+        Some(monitor) => window.center_on(&monitor),
+        None => window.center(),
+      }
     }
 
     let window_id = window.id();
@@ -479,6 +1165,123 @@ impl AppWindowBuilder {
         .replace(window_id);
     }
 
-    window
+    if let (true, Some(parent)) = (window.modal, &self.parent) {
+      begin_modal(&window, parent);
+      parent.set_input_enabled(false);
+    }
+
+    Ok(window)
   }
 }
+
+/// Makes the native window owned by `parent`, so it stays on top of it.
+fn with_owner(
+  builder: tao::window::WindowBuilder,
+  parent: &AppWindow,
+) -> tao::window::WindowBuilder {
+  #[cfg(target_os = "windows")]
+  {
+    use tao::platform::windows::{WindowBuilderExtWindows, WindowExtWindows};
+    builder.with_owner_window(parent.tao_window.hwnd())
+  }
+
+  #[cfg(target_os = "macos")]
+  {
+    use tao::platform::macos::{WindowBuilderExtMacOS, WindowExtMacOS};
+    builder.with_parent_window(parent.tao_window.ns_window())
+  }
+
+  #[cfg(any(target_os = "ios", target_os = "android"))]
+  {
+    let _ = parent;
+    builder
+  }
+
+  #[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "android"
+  )))]
+  {
+    use tao::platform::unix::{WindowBuilderExtUnix, WindowExtUnix};
+    builder.with_transient_for(parent.tao_window.gtk_window())
+  }
+}
+
+/// Makes `window` modal to `parent` natively: a sheet on macOS and a modal
+/// transient window on Linux, which the window manager keeps above the parent.
+/// Windows disables the parent instead, see `set_input_enabled`.
+fn begin_modal(window: &AppWindow, parent: &AppWindow) {
+  #[cfg(target_os = "macos")]
+  {
+    use objc::{msg_send, runtime::Object, sel, sel_impl};
+    use tao::platform::macos::WindowExtMacOS;
+
+    let sheet = window.tao_window.ns_window() as *mut Object;
+    let parent = parent.tao_window.ns_window() as *mut Object;
+
+    unsafe {
+      let _: () =
+        msg_send![parent, beginSheet: sheet completionHandler: std::ptr::null_mut::<Object>()];
+    }
+  }
+
+  #[cfg(any(target_os = "windows", target_os = "ios", target_os = "android"))]
+  {
+    let _ = (window, parent);
+  }
+
+  #[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "android"
+  )))]
+  {
+    use gtk::prelude::GtkWindowExt;
+    use tao::platform::unix::WindowExtUnix;
+
+    let _ = parent;
+    window.tao_window.gtk_window().set_modal(true);
+  }
+}
+
+/// Detaches a modal window from its parent before it closes, see
+/// `begin_modal`. Must be called from the event loop thread.
+pub fn end_modal(window: &AppWindow, parent: &AppWindow) {
+  #[cfg(target_os = "macos")]
+  {
+    use objc::{msg_send, runtime::Object, sel, sel_impl};
+    use tao::platform::macos::WindowExtMacOS;
+
+    let sheet = window.tao_window.ns_window() as *mut Object;
+    let parent = parent.tao_window.ns_window() as *mut Object;
+
+    unsafe {
+      let _: () = msg_send![parent, endSheet: sheet];
+    }
+  }
+
+  #[cfg(not(target_os = "macos"))]
+  {
+    let _ = (window, parent);
+  }
+}
+
+/// Ids of the children of `window_id`, their children and so on.
+pub fn descendant_ids(windows: &HashMap<u32, AppWindow>, window_id: u32) -> Vec<u32> {
+  let mut ids = Vec::new();
+  let mut parents = vec![window_id];
+
+  while let Some(parent_id) = parents.pop() {
+    for (id, window) in windows {
+      if window.parent_id == Some(parent_id) {
+        ids.push(*id);
+        parents.push(*id);
+      }
+    }
+  }
+
+  ids
+}
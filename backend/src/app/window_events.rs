@@ -0,0 +1,114 @@
+use serde_json::json;
+use tao::{
+  dpi::{PhysicalPosition, PhysicalSize},
+  event::WindowEvent,
+  window::Theme,
+};
+
+use super::window::{AppWindow, AppWindowExt};
+
+/// Last minimized and maximized state reported to the page, since tao only
+/// signals those changes through `Resized`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WindowStateFlags {
+  minimized: bool,
+  maximized: bool,
+}
+
+/// Emits tao window events to the window they concern as `window.*` events.
+/// Sizes and positions are physical pixels, along with the scale factor.
+pub fn forward_window_event(window: &AppWindow, event: &WindowEvent) {
+  let scale_factor = window.tao_window.scale_factor();
+  let mut events = Vec::new();
+
+  match event {
+    WindowEvent::Focused(true) => events.push(("window.focused", json!({}))),
+    WindowEvent::Focused(false) => events.push(("window.blurred", json!({}))),
+    WindowEvent::Resized(size) => {
+      events.push(("window.resized", size_payload(*size, scale_factor)));
+      events.extend(state_changes(window));
+    }
+    WindowEvent::Moved(position) => {
+      events.push(("window.moved", position_payload(*position, scale_factor)))
+    }
+    WindowEvent::ScaleFactorChanged {
+      scale_factor,
+      new_inner_size,
+    } => events.push((
+      "window.scale_factor_changed",
+      size_payload(**new_inner_size, *scale_factor),
+    )),
+    WindowEvent::ThemeChanged(theme) => events.push((
+      "window.theme_changed",
+      json!({ "theme": theme_name(*theme) }),
+    )),
+    _ => {}
+  }
+
+  for (name, payload) in events {
+    window.emit(name, payload);
+  }
+}
+
+/// Emits `window.frame_changed` after the decorations or resizability of the
+/// window changed, so frameless pages can update their resize edges.
+pub fn emit_frame_changed(window: &AppWindow) {
+  window.emit(
+    "window.frame_changed",
+    json!({
+      "decorated": window.is_decorated(),
+      "resizable": window.is_resizable(),
+    }),
+  );
+}
+
+fn theme_name(theme: Theme) -> &'static str {
+  match theme {
+    Theme::Dark => "dark",
+    _ => "light",
+  }
+}
+
+fn size_payload(size: PhysicalSize<u32>, scale_factor: f64) -> serde_json::Value {
+  json!({
+    "width": size.width,
+    "height": size.height,
+    "scale_factor": scale_factor,
+  })
+}
+
+fn position_payload(position: PhysicalPosition<i32>, scale_factor: f64) -> serde_json::Value {
+  json!({
+    "x": position.x,
+    "y": position.y,
+    "scale_factor": scale_factor,
+  })
+}
+
+fn state_changes(window: &AppWindow) -> Option<(&'static str, serde_json::Value)> {
+  let current = WindowStateFlags {
+    minimized: window.tao_window.is_minimized(),
+    maximized: window.tao_window.is_maximized(),
+  };
+  let previous = std::mem::replace(
+    &mut *window
+      .state_flags
+      .lock()
+      .expect("Window state lock is poisoned"),
+    current,
+  );
+
+  if current == previous {
+    return None;
+  }
+
+  let name = if current.minimized {
+    "window.minimized"
+  } else if current.maximized {
+    "window.maximized"
+  } else {
+    "window.restored"
+  };
+
+  Some((name, json!({})))
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tao::{
+  dpi::{PhysicalPosition, PhysicalSize},
+  window::Window,
+};
+
+use super::{
+  app_paths,
+  monitors::{self, Rect},
+  window::{AppWindow, AppWindowExt},
+};
+
+/// Geometry of a window when it was last closed, in physical pixels. While
+/// maximized, the size and position are the ones it restores to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowGeometry {
+  pub width: u32,
+  pub height: u32,
+  pub x: i32,
+  pub y: i32,
+  pub maximized: bool,
+  pub monitor: Option<String>,
+}
+
+fn load_all() -> HashMap<String, WindowGeometry> {
+  std::fs::read_to_string(app_paths::window_state())
+    .ok()
+    .and_then(|state| serde_json::from_str(&state).ok())
+    .unwrap_or_default()
+}
+
+pub fn load(key: &str) -> Option<WindowGeometry> {
+  load_all().remove(key)
+}
+
+/// Saves the geometry of a window built `with_restore_state`, keeping the
+/// restored size and position of a maximized window from the previous save.
+/// A minimized window reports a meaningless geometry on some platforms, so
+/// the previous save is kept as is.
+pub fn save(window: &AppWindow) {
+  let Some(key) = window.restore_state.as_deref() else {
+    return;
+  };
+
+  if window.tao_window.is_minimized() {
+    return;
+  }
+
+  let path = app_paths::window_state();
+  let mut state = load_all();
+  let maximized = window.is_maximized();
+  let geometry = match state.get(key) {
+    Some(previous) if maximized => WindowGeometry {
+      maximized,
+      ..previous.clone()
+    },
+    _ => {
+      let PhysicalSize { width, height } = window.size();
+      let PhysicalPosition { x, y } = window.position();
+
+      WindowGeometry {
+        width,
+        height,
+        x,
+        y,
+        maximized,
+        monitor: window
+          .tao_window
+          .current_monitor()
+          .and_then(|monitor| monitor.name()),
+      }
+    }
+  };
+
+  state.insert(key.to_string(), geometry);
+
+  let result = path
+    .parent()
+    .map_or(Ok(()), std::fs::create_dir_all)
+    .and_then(|_| {
+      std::fs::write(
+        &path,
+        serde_json::to_string_pretty(&state).expect("Failed to serialize window state"),
+      )
+    });
+
+  if let Err(err) = result {
+    eprintln!(
+      "Failed to save window state to {} > {}",
+      path.display(),
+      err
+    );
+  }
+}
+
+/// Applies a saved geometry, moved onto a connected monitor when the one it
+/// was saved on is gone and shrunk to fit it. Returns false when no monitor
+/// is available.
+pub fn restore(tao_window: &Window, geometry: &WindowGeometry) -> bool {
+  let primary = tao_window.primary_monitor();
+  let monitors: Vec<SavedMonitor> = tao_window
+    .available_monitors()
+    .map(|monitor| SavedMonitor {
+      primary: primary.as_ref() == Some(&monitor),
+      name: monitor.name(),
+      bounds: monitors::bounds(&monitor),
+    })
+    .collect();
+
+  let Some(area) = fit(geometry, &monitors) else {
+    return false;
+  };
+
+  tao_window.set_inner_size(PhysicalSize::new(area.width, area.height));
+  tao_window.set_outer_position(PhysicalPosition::new(area.x, area.y));
+
+  if geometry.maximized {
+    tao_window.set_maximized(true);
+  }
+
+  true
+}
+
+/// What `fit` needs to know about a connected monitor.
+struct SavedMonitor {
+  name: Option<String>,
+  primary: bool,
+  bounds: Rect,
+}
+
+/// Size and position of `geometry` on the monitor it was saved on, or else
+/// the one containing its position, the primary one or the first one. The
+/// window is shrunk to fit the monitor and moved onto it.
+fn fit(geometry: &WindowGeometry, monitors: &[SavedMonitor]) -> Option<Rect> {
+  let monitor = monitors
+    .iter()
+    .find(|monitor| geometry.monitor.is_some() && monitor.name == geometry.monitor)
+    .or_else(|| {
+      monitors
+        .iter()
+        .find(|monitor| contains(monitor.bounds, geometry))
+    })
+    .or_else(|| monitors.iter().find(|monitor| monitor.primary))
+    .or_else(|| monitors.first())?;
+
+  let area = monitor.bounds;
+  let size = PhysicalSize::new(
+    geometry.width.min(area.width),
+    geometry.height.min(area.height),
+  );
+  let position =
+    monitors::clamp_position(area, PhysicalPosition::new(geometry.x, geometry.y), size);
+
+  Some(Rect {
+    x: position.x,
+    y: position.y,
+    width: size.width,
+    height: size.height,
+  })
+}
+
+fn contains(area: Rect, geometry: &WindowGeometry) -> bool {
+  geometry.x >= area.x
+    && geometry.y >= area.y
+    && geometry.x < area.x + area.width as i32
+    && geometry.y < area.y + area.height as i32
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn geometry(x: i32, y: i32, width: u32, height: u32, monitor: &str) -> WindowGeometry {
+    WindowGeometry {
+      width,
+      height,
+      x,
+      y,
+      maximized: false,
+      monitor: Some(monitor.to_string()),
+    }
+  }
+
+  fn monitor(name: &str, x: i32, width: u32, primary: bool) -> SavedMonitor {
+    SavedMonitor {
+      name: Some(name.to_string()),
+      primary,
+      bounds: Rect {
+        x,
+        y: 0,
+        width,
+        height: 1080,
+      },
+    }
+  }
+
+  fn rect(fitted: Option<Rect>) -> Option<(i32, i32, u32, u32)> {
+    fitted.map(|area| (area.x, area.y, area.width, area.height))
+  }
+
+  #[test]
+  fn shrinks_windows_larger_than_the_monitor() {
+    let monitors = [monitor("DP-1", 0, 1920, true)];
+
+    assert_eq!(
+      rect(fit(&geometry(100, 50, 3000, 2000, "DP-1"), &monitors)),
+      Some((0, 0, 1920, 1080))
+    );
+    assert_eq!(
+      rect(fit(&geometry(1500, 50, 800, 2000, "DP-1"), &monitors)),
+      Some((1120, 0, 800, 1080))
+    );
+  }
+
+  #[test]
+  fn restores_on_the_saved_monitor() {
+    let monitors = [
+      monitor("DP-1", 0, 1920, true),
+      monitor("DP-2", 1920, 1280, false),
+    ];
+
+    assert_eq!(
+      rect(fit(&geometry(2000, 100, 800, 600, "DP-2"), &monitors)),
+      Some((2000, 100, 800, 600))
+    );
+  }
+
+  #[test]
+  fn moves_windows_of_removed_monitors_onto_a_connected_one() {
+    let monitors = [
+      monitor("DP-3", -1280, 1280, false),
+      monitor("DP-1", 0, 1920, true),
+    ];
+
+    // Off every connected monitor: moved onto the primary one.
+    assert_eq!(
+      rect(fit(&geometry(2000, 100, 800, 600, "DP-2"), &monitors)),
+      Some((1120, 100, 800, 600))
+    );
+    // Still on a connected monitor: kept there.
+    assert_eq!(
+      rect(fit(&geometry(-1000, 100, 800, 600, "DP-2"), &monitors)),
+      Some((-1000, 100, 800, 600))
+    );
+  }
+
+  #[test]
+  fn needs_a_monitor() {
+    assert!(fit(&geometry(0, 0, 800, 600, "DP-1"), &[]).is_none());
+  }
+}
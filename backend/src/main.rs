@@ -11,9 +11,83 @@ mod app;
 mod plugins;
 mod state;
 
+/// Value following `name` on the command line, or an empty string when the
+/// flag is the last argument.
+fn cli_option(name: &str) -> Option<String> {
+  let mut args = std::env::args().skip(1);
+
+  while let Some(arg) = args.next() {
+    if arg == name {
+      return Some(args.next().unwrap_or_default());
+    }
+  }
+
+  None
+}
+
+/// Whether `name` is on the command line.
+fn cli_flag(name: &str) -> bool {
+  std::env::args().skip(1).any(|arg| arg == name)
+}
+
 #[tokio::main]
 async fn main() {
-  let event_loop = tao::event_loop::EventLoopBuilder::<AppWindowEvent>::with_user_event().build();
+  // `--emit-typings <dir>` writes `lenz.d.ts` and exits. It runs before the
+  // event loop is built, so it works without a display.
+  if let Some(dir) = cli_option("--emit-typings") {
+    let dir = if dir.is_empty() { ".".to_string() } else { dir };
+    let app = app::Application::headless(state::AppState::new());
+
+    plugins::apply(app.clone());
+
+    match app::typings::write_typings(&app, std::path::Path::new(&dir)) {
+      Ok(path) => println!("Typings written to {}", path.display()),
+      Err(err) => eprintln!("Failed to write typings > {}", err),
+    }
+
+    return;
+  }
+
+  // `--replay-trace <file>` re-issues the invokes of a recorded trace without
+  // opening windows or running the event loop, and exits with a non-zero code
+  // when results differ. Methods with side effects, like `fs.write`, are
+  // skipped unless `--replay-side-effects` is passed.
+  if let Some(path) = cli_option("--replay-trace") {
+    let app = app::Application::headless(state::AppState::new());
+
+    plugins::apply(app.clone());
+
+    let replay = app::trace::replay(
+      app,
+      std::path::Path::new(&path),
+      cli_flag("--replay-side-effects"),
+    );
+
+    match replay.await {
+      Ok(summary) => {
+        println!(
+          "[replay] {} invokes, {} mismatches, {} skipped",
+          summary.invokes, summary.mismatches, summary.skipped
+        );
+        std::process::exit(if summary.mismatches == 0 { 0 } else { 1 });
+      }
+      Err(err) => {
+        eprintln!("Failed to replay trace {} > {}", path, err);
+        std::process::exit(1);
+      }
+    }
+  }
+
+  let mut event_loop_builder =
+    tao::event_loop::EventLoopBuilder::<AppWindowEvent>::with_user_event();
+
+  #[cfg(target_os = "windows")]
+  {
+    use tao::platform::windows::EventLoopBuilderExtWindows;
+    event_loop_builder.with_msg_hook(app::menu::translate_accelerator);
+  }
+
+  let event_loop = event_loop_builder.build();
   let app = app::Application::new(&event_loop, state::AppState::new());
 
   plugins::apply(app.clone());
@@ -32,6 +106,17 @@ async fn main() {
 
   println!("Base URL: {}", base_url);
 
+  // `LENZ_IPC_BRIDGE=<port>` serves IPC over localhost HTTP, so the UI can be
+  // debugged in a regular browser.
+  #[cfg(debug_assertions)]
+  if let Ok(port) = std::env::var("LENZ_IPC_BRIDGE") {
+    app::bridge::start(
+      app.clone(),
+      port.parse().unwrap_or(app::bridge::DEFAULT_BRIDGE_PORT),
+      &base_url,
+    );
+  }
+
   let main = app
     .build_window()
     .main()
@@ -39,21 +124,35 @@ async fn main() {
     .with_title("Lenz")
     .with_url(&base_url)
     .with_devtools()
+    .with_restore_state("main")
+    .with_menu_bar()
     .at_center()
     .build(&event_loop);
 
-  let splash = main
+  let splash = app
     .build_window()
     .with_label("splash")
     .with_visible(true)
     .with_transparent(true)
     .with_decorations(false)
     .with_closable(false)
+    .with_resizable(false)
     .with_size(400.0, 400.0)
     .at_center()
     .with_url(format!("{}/splash.html", base_url))
     .build(&event_loop);
 
+  // Plugins can also listen to `app.ready` to run once startup is done.
+  app.once("app.ready", {
+    let main = main.clone();
+    let splash = splash.clone();
+
+    move |_| {
+      main.show();
+      splash.close();
+    }
+  });
+
   let app2 = app.clone();
 
   tokio::task::spawn(async move {
@@ -78,15 +177,16 @@ async fn main() {
 
       extension.activate(app2.clone());
 
-      splash.emit("extension.activated", extension_json.clone());
+      let mut target = vec![splash.id()];
 
       if has_main_script {
-        main.emit("extension.activated", extension_json.clone());
+        target.push(main.id());
       }
+
+      app2.emit_to("extension.activated", extension_json, target);
     }
 
-    main.show();
-    splash.close();
+    app2.emit_to("app.ready", json!({}), vec![main.id()]);
   });
 
   event_loop.run(move |event, event_loop, control_flow| {
@@ -1,143 +1,95 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
 use serde_json::json;
 
 use crate::{
   app::{
-    invoke::{InvokeCommand, InvokeResult},
-    window::AppWindowExt,
+    invoke::InvokeResult,
+    window::{AppWindow, AppWindowExt},
     App, AppExt,
   },
   async_invoke_handlers,
 };
 
-async fn dialog_show(command: InvokeCommand) -> InvokeResult {
-  let options = if let Some(options) = command.args.first() {
-    if let Some(options) = options.as_object() {
-      options
-    } else {
-      return InvokeResult::error("Dialog Options must be an object");
-    }
-  } else {
-    return InvokeResult::error("Dialog Options not provided");
-  };
-
-  let title = options.get("title").and_then(|v| v.as_str());
-  let message = options.get("message").and_then(|v| v.as_str());
-  let level = options
-    .get("level")
-    .and_then(|v| v.as_str())
-    .unwrap_or("info");
-
-  let mut builder = rfd::MessageDialog::new();
-
-  if let Some(title) = title {
-    builder = builder.set_title(title);
-  }
-
-  if let Some(message) = message {
-    builder = builder.set_description(message);
-  }
-
-  let level = match level {
-    "warning" => rfd::MessageLevel::Warning,
-    "error" => rfd::MessageLevel::Error,
-    _ => rfd::MessageLevel::Info,
-  };
-
-  builder
-    .set_parent(&command.window.window_handle())
-    .set_level(level)
-    .show();
+#[derive(Deserialize)]
+struct MessageDialogOptions {
+  title: Option<String>,
+  message: Option<String>,
+  level: Option<String>,
+}
 
-  ().into()
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileDialogOptions {
+  title: Option<String>,
+  default_path: Option<String>,
+  default_file_name: Option<String>,
+  #[serde(default)]
+  filters: BTreeMap<String, Vec<String>>,
+  #[serde(default)]
+  multiple: bool,
+  #[serde(default)]
+  can_create_directories: bool,
 }
 
-async fn dialog_confirm(command: InvokeCommand) -> InvokeResult {
-  let options = if let Some(options) = command.args.first() {
-    if let Some(options) = options.as_object() {
-      options
-    } else {
-      return InvokeResult::error("Dialog Options must be an object");
+impl MessageDialogOptions {
+  fn into_dialog(self, window: &AppWindow) -> rfd::MessageDialog {
+    let mut builder = rfd::MessageDialog::new();
+
+    if let Some(title) = self.title {
+      builder = builder.set_title(title);
     }
-  } else {
-    return InvokeResult::error("Dialog Options not provided");
-  };
 
-  let title = options.get("title").and_then(|v| v.as_str());
-  let message = options.get("message").and_then(|v| v.as_str());
-  let level = options
-    .get("level")
-    .and_then(|v| v.as_str())
-    .unwrap_or("info");
+    if let Some(message) = self.message {
+      builder = builder.set_description(message);
+    }
 
-  let mut builder = rfd::MessageDialog::new();
+    let level = match self.level.as_deref() {
+      Some("warning") => rfd::MessageLevel::Warning,
+      Some("error") => rfd::MessageLevel::Error,
+      _ => rfd::MessageLevel::Info,
+    };
 
-  if let Some(title) = title {
-    builder = builder.set_title(title);
+    builder.set_parent(&window.window_handle()).set_level(level)
   }
+}
 
-  if let Some(message) = message {
-    builder = builder.set_description(message);
+impl FileDialogOptions {
+  fn filters(&self) -> impl Iterator<Item = (&String, Vec<&str>)> {
+    self
+      .filters
+      .iter()
+      .map(|(name, patterns)| (name, patterns.iter().map(String::as_str).collect()))
   }
+}
 
-  let level = match level {
-    "warning" => rfd::MessageLevel::Warning,
-    "error" => rfd::MessageLevel::Error,
-    _ => rfd::MessageLevel::Info,
-  };
+async fn dialog_show(window: AppWindow, options: MessageDialogOptions) -> InvokeResult {
+  options.into_dialog(&window).show();
 
-  let result = builder
-    .set_parent(&command.window.window_handle())
-    .set_level(level)
+  ().into()
+}
+
+async fn dialog_confirm(window: AppWindow, options: MessageDialogOptions) -> InvokeResult {
+  let result = options
+    .into_dialog(&window)
     .set_buttons(rfd::MessageButtons::YesNo)
     .show();
 
   (result == rfd::MessageDialogResult::Yes).into()
 }
 
-async fn dialog_files_open(command: InvokeCommand) -> InvokeResult {
-  let options = if let Some(options) = command.args.first() {
-    if let Some(options) = options.as_object() {
-      options
-    } else {
-      return InvokeResult::error("Dialog Options must be an object");
-    }
-  } else {
-    return InvokeResult::error("Dialog Options not provided");
-  };
-
-  let title = options.get("title").and_then(|v| v.as_str());
-  let default_path = options.get("defaultPath").and_then(|v| v.as_str());
-  let filters = options
-    .get("filters")
-    .and_then(|v| v.as_object())
-    .map(|filters| {
-      filters
-        .iter()
-        .map(|(name, pattern)| {
-          (
-            name.as_str(),
-            pattern
-              .as_array()
-              .map(|v| v.iter().filter_map(|v| v.as_str()).collect::<Vec<&str>>()),
-          )
-        })
-        .collect::<Vec<_>>()
-    });
-
+async fn dialog_files_open(window: AppWindow, options: FileDialogOptions) -> InvokeResult {
   let mut builder = rfd::FileDialog::new()
-    .set_parent(&command.window.window_handle())
-    .set_title(title.unwrap_or("Open File"))
-    .set_directory(default_path.unwrap_or(""));
+    .set_parent(&window.window_handle())
+    .set_title(options.title.as_deref().unwrap_or("Open File"))
+    .set_directory(options.default_path.as_deref().unwrap_or(""));
 
-  for (name, patterns) in filters.unwrap_or_default() {
-    builder = builder.add_filter(name, patterns.unwrap_or_default().as_slice());
+  for (name, patterns) in options.filters() {
+    builder = builder.add_filter(name, patterns.as_slice());
   }
 
-  if options
-    .get("multiple")
-    .and_then(|v| v.as_bool())
-    .unwrap_or(false)
-  {
+  if options.multiple {
     builder
       .pick_files()
       .map_or_else(std::vec::Vec::new, |paths| {
@@ -158,57 +110,19 @@ async fn dialog_files_open(command: InvokeCommand) -> InvokeResult {
   }
 }
 
-async fn dialog_files_save(command: InvokeCommand) -> InvokeResult {
-  let options = if let Some(options) = command.args.first() {
-    if let Some(options) = options.as_object() {
-      options
-    } else {
-      return InvokeResult::error("Dialog Options must be an object");
-    }
-  } else {
-    return InvokeResult::error("Dialog Options not provided");
-  };
-
-  let title = options.get("title").and_then(|v| v.as_str());
-  let default_path = options.get("defaultPath").and_then(|v| v.as_str());
-  let filters = options
-    .get("filters")
-    .and_then(|v| v.as_object())
-    .map(|filters| {
-      filters
-        .iter()
-        .map(|(name, pattern)| {
-          (
-            name.as_str(),
-            pattern
-              .as_array()
-              .map(|v| v.iter().filter_map(|v| v.as_str()).collect::<Vec<&str>>()),
-          )
-        })
-        .collect::<Vec<_>>()
-    });
-  let can_create_directories = options
-    .get("canCreateDirectories")
-    .and_then(|v| v.as_bool())
-    .unwrap_or(false);
-
-  let file_name = options
-    .get("defaultFileName")
-    .and_then(|v| v.as_str())
-    .map(|v| v.to_string());
-
+async fn dialog_files_save(window: AppWindow, options: FileDialogOptions) -> InvokeResult {
   let mut builder = rfd::FileDialog::new()
-    .set_parent(&command.window.window_handle())
-    .set_title(title.unwrap_or("Save File"))
-    .set_directory(default_path.unwrap_or(""))
-    .set_can_create_directories(can_create_directories);
+    .set_parent(&window.window_handle())
+    .set_title(options.title.as_deref().unwrap_or("Save File"))
+    .set_directory(options.default_path.as_deref().unwrap_or(""))
+    .set_can_create_directories(options.can_create_directories);
 
-  if let Some(file_name) = file_name {
+  if let Some(file_name) = &options.default_file_name {
     builder = builder.set_file_name(file_name);
   }
 
-  for (name, patterns) in filters.unwrap_or_default() {
-    builder = builder.add_filter(name, patterns.unwrap_or_default().as_slice());
+  for (name, patterns) in options.filters() {
+    builder = builder.add_filter(name, patterns.as_slice());
   }
 
   builder.save_file().map_or_else(
@@ -217,35 +131,14 @@ async fn dialog_files_save(command: InvokeCommand) -> InvokeResult {
   )
 }
 
-async fn dialog_select_folder(command: InvokeCommand) -> impl Into<InvokeResult> {
-  let options = if let Some(options) = command.args.first() {
-    if let Some(options) = options.as_object() {
-      options
-    } else {
-      return InvokeResult::error("Dialog Options must be an object");
-    }
-  } else {
-    return InvokeResult::error("Dialog Options not provided");
-  };
-
-  let title = options.get("title").and_then(|v| v.as_str());
-  let default_path = options.get("defaultPath").and_then(|v| v.as_str());
-  let can_create_directories = options
-    .get("canCreateDirectories")
-    .and_then(|v| v.as_bool())
-    .unwrap_or(false);
-
+async fn dialog_select_folder(window: AppWindow, options: FileDialogOptions) -> InvokeResult {
   let builder = rfd::AsyncFileDialog::new()
-    .set_parent(&command.window.window_handle())
-    .set_title(title.unwrap_or("Open Folder"))
-    .set_directory(default_path.unwrap_or(""))
-    .set_can_create_directories(can_create_directories);
+    .set_parent(&window.window_handle())
+    .set_title(options.title.as_deref().unwrap_or("Open Folder"))
+    .set_directory(options.default_path.as_deref().unwrap_or(""))
+    .set_can_create_directories(options.can_create_directories);
 
-  if options
-    .get("multiple")
-    .and_then(|v| v.as_bool())
-    .unwrap_or(false)
-  {
+  if options.multiple {
     match builder.pick_folders().await {
       Some(file_handlers) => file_handlers
         .into_iter()
@@ -277,10 +170,10 @@ async fn dialog_select_folder(command: InvokeCommand) -> impl Into<InvokeResult>
 
 pub fn apply(app: App) {
   async_invoke_handlers!(app, {
-    "dialog.show" => dialog_show,
-    "dialog.confirm" => dialog_confirm,
+    "dialog.show" => dialog_show: (),
+    "dialog.confirm" => dialog_confirm: bool,
     "dialog.files.open" => dialog_files_open,
-    "dialog.files.save" => dialog_files_save,
+    "dialog.files.save" => dialog_files_save: Option<String>,
     "dialog.folder.select" => dialog_select_folder
   });
 }
@@ -1,61 +1,202 @@
 use crate::{
   app::{
-    invoke::{InvokeCommand, InvokeResult},
+    channel::Channel,
+    invoke::{InvokeCommand, InvokeError, InvokeResult, InvokeStream},
+    limits::InvokeLimits,
+    schema,
+    window::AppWindow,
     App, AppExt,
   },
   async_invoke_handlers,
 };
-use std::io::Write;
-
-async fn fs_read(command: InvokeCommand) -> InvokeResult {
-  if let Some(path) = command.args.first() {
-    let path = if let Some(path) = path.as_str() {
-      path.trim()
-    } else {
-      return InvokeResult::error("Path must be a string");
-    };
-
-    if path.is_empty() {
-      return InvokeResult::error("Path cannot be empty");
-    }
+use serde::{Deserialize, Serialize};
+use std::{io::Write, time::Duration, time::SystemTime};
+use tokio::io::AsyncReadExt;
 
-    if let Ok(content) = std::fs::read(path) {
-      return InvokeResult::binary(content);
-    }
+const READ_STREAM_CHUNK_SIZE: usize = 64 * 1024;
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_CONCURRENT_OPERATIONS: usize = 16;
+const MAX_QUEUED_OPERATIONS: usize = 256;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum WatchEventKind {
+  Created,
+  Modified,
+  Removed,
+  Error,
+}
+
+/// A plain struct rather than a tagged enum, so its shape can be traced for
+/// the typings.
+#[derive(Serialize, Deserialize)]
+struct WatchEvent {
+  kind: WatchEventKind,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  error: Option<InvokeError>,
+}
 
-    return InvokeResult::error("File not found!");
+impl From<WatchEventKind> for WatchEvent {
+  fn from(kind: WatchEventKind) -> Self {
+    Self { kind, error: None }
+  }
+}
+
+fn validate_path(path: &str) -> Result<&str, InvokeError> {
+  let path = path.trim();
+
+  if path.is_empty() {
+    return Err(InvokeError::invalid_argument("Path cannot be empty"));
   }
 
-  InvokeResult::error("Path is required")
+  Ok(path)
 }
 
-async fn fs_write(command: InvokeCommand) -> InvokeResult {
-  let filename = if let Some(filename) = command.args.first() {
-    filename.as_str().unwrap()
-  } else {
-    return InvokeResult::error("Invalid filename");
+async fn fs_read(path: String) -> InvokeResult {
+  let path = match validate_path(&path) {
+    Ok(path) => path,
+    Err(err) => return InvokeResult::error(err),
   };
 
-  let content = if let Some(content) = command.args.get(1) {
-    content.as_str().unwrap()
-  } else {
-    return InvokeResult::error("Invalid content");
+  match tokio::fs::read(path).await {
+    Ok(content) => InvokeResult::binary(content),
+    Err(err) => InvokeResult::error(err),
+  }
+}
+
+async fn fs_read_stream(path: String) -> InvokeResult {
+  let path = match validate_path(&path) {
+    Ok(path) => path,
+    Err(err) => return InvokeResult::error(err),
+  };
+
+  let mut file = match tokio::fs::File::open(path).await {
+    Ok(file) => file,
+    Err(err) => return InvokeResult::error(err),
   };
 
-  if let Ok(mut file) = std::fs::File::create(filename) {
-    if let Err(err) = file.write_all(content.as_bytes()) {
-      return InvokeResult::Err(err.to_string());
+  let length = file.metadata().await.ok().map(|metadata| metadata.len());
+  let (sender, stream) = InvokeStream::channel(length);
+
+  tokio::task::spawn(async move {
+    let mut buffer = vec![0; READ_STREAM_CHUNK_SIZE];
+
+    loop {
+      match file.read(&mut buffer).await {
+        Ok(0) => break,
+        Ok(size) => {
+          if !sender.send(buffer[..size].to_vec()).await {
+            break;
+          }
+        }
+        Err(err) => {
+          sender.fail(err).await;
+          break;
+        }
+      }
     }
+  });
 
-    return InvokeResult::json(serde_json::Value::Null);
+  InvokeResult::stream(stream)
+}
+
+async fn modified_at(path: &str) -> std::io::Result<Option<SystemTime>> {
+  match tokio::fs::metadata(path).await {
+    Ok(metadata) => metadata.modified().map(Some),
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(err) => Err(err),
   }
+}
 
-  InvokeResult::error("Failed to write file")
+async fn fs_watch(window: AppWindow, path: String) -> InvokeResult {
+  let path = match validate_path(&path) {
+    Ok(path) => path.to_string(),
+    Err(err) => return InvokeResult::error(err),
+  };
+
+  let mut modified = match modified_at(&path).await {
+    Ok(modified) => modified,
+    Err(err) => return InvokeResult::error(err),
+  };
+
+  let channel = Channel::<WatchEvent>::open(&window);
+  let producer = channel.clone();
+
+  tokio::task::spawn(async move {
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+
+    loop {
+      tokio::select! {
+        _ = producer.closed() => break,
+        _ = interval.tick() => {}
+      }
+
+      let current = match modified_at(&path).await {
+        Ok(current) => current,
+        Err(err) => {
+          producer.send(WatchEvent {
+            kind: WatchEventKind::Error,
+            error: Some(err.into()),
+          });
+          producer.close();
+          break;
+        }
+      };
+
+      let event = match (modified, current) {
+        (None, Some(_)) => Some(WatchEventKind::Created),
+        (Some(_), None) => Some(WatchEventKind::Removed),
+        (Some(previous), Some(current)) if previous != current => Some(WatchEventKind::Modified),
+        _ => None,
+      };
+
+      modified = current;
+
+      if let Some(event) = event {
+        if !producer.send(event.into()) {
+          break;
+        }
+      }
+    }
+  });
+
+  channel.into()
+}
+
+async fn fs_write(command: InvokeCommand) -> InvokeResult {
+  let filename = if let Some(filename) = command.args.first().and_then(|v| v.as_str()) {
+    filename
+  } else {
+    return InvokeResult::error(InvokeError::invalid_argument("Invalid filename"));
+  };
+
+  let content = if let Some(content) = command.blob(1) {
+    content
+  } else if let Some(content) = command.args.get(1).and_then(|v| v.as_str()) {
+    content.as_bytes()
+  } else {
+    return InvokeResult::error(InvokeError::invalid_argument("Invalid content"));
+  };
+
+  std::fs::File::create(filename)
+    .and_then(|mut file| file.write_all(content))
+    .map(|_| serde_json::Value::Null)
+    .map_err(InvokeError::from)
+    .into()
 }
 
 pub fn apply(app: App) {
+  app.set_invoke_limits(
+    "fs.*",
+    InvokeLimits::new()
+      .with_max_concurrent(MAX_CONCURRENT_OPERATIONS)
+      .with_max_queued(MAX_QUEUED_OPERATIONS),
+  );
+
   async_invoke_handlers!(app, {
-    "fs.read" => fs_read,
-    "fs.write" => fs_write
+    "fs.read" => fs_read: schema::Binary,
+    "fs.read_stream" => fs_read_stream: schema::Stream,
+    "fs.watch" => fs_watch: schema::Subscription<WatchEvent>,
+    "fs.write" => fs_write: ()
   });
 }
@@ -0,0 +1,260 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+  app::{
+    channel,
+    invoke::{
+      register_stream, InvokeCommand, InvokeError, InvokeResponder, InvokeResult, InvokeResultData,
+      INVOKE_BLOB_KEY,
+    },
+    invoke_args::{invoke_arg, NO_WINDOW_CODE},
+    limits::InvokeLimits,
+    schema::describe_invoke_methods,
+    window::{AppWindow, AppWindowEvent, AppWindowExt},
+    App, AppExt,
+  },
+  async_invoke_handlers,
+};
+
+#[derive(Deserialize)]
+struct BatchCall {
+  method: String,
+  #[serde(default)]
+  args: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum BatchMode {
+  #[default]
+  Sequential,
+  Parallel,
+}
+
+#[derive(Deserialize)]
+struct CallReply {
+  ok: bool,
+  value: Option<serde_json::Value>,
+  error: Option<InvokeError>,
+}
+
+#[derive(Deserialize, Default)]
+struct BatchOptions {
+  #[serde(default)]
+  mode: BatchMode,
+}
+
+/// Binary results are moved to `blobs` and replaced by a `$blob` placeholder,
+/// so the batch can be sent as a mixed response.
+fn batch_entry(
+  window: Option<&AppWindow>,
+  blobs: &mut Vec<Vec<u8>>,
+  result: Result<InvokeResult, InvokeError>,
+) -> serde_json::Value {
+  match result {
+    Ok(InvokeResult::Ok(InvokeResultData::Json(value))) => json!({ "ok": true, "value": value }),
+    Ok(InvokeResult::Ok(InvokeResultData::Binary(value))) => {
+      blobs.push(value);
+
+      json!({ "ok": true, "value": { INVOKE_BLOB_KEY: blobs.len() - 1 } })
+    }
+    Ok(InvokeResult::Ok(InvokeResultData::Mixed { .. })) => json!({
+      "ok": false,
+      "error": InvokeError::invalid_argument("Nested batches cannot return binary results"),
+    }),
+    Ok(InvokeResult::Ok(InvokeResultData::Stream(stream))) => match window {
+      Some(window) => {
+        let length = stream.length();
+
+        json!({ "ok": true, "stream": register_stream(&window.app, window.id(), stream), "length": length })
+      }
+      None => json!({
+        "ok": false,
+        "error": InvokeError::new(NO_WINDOW_CODE, "Streams can only be pulled by a window"),
+      }),
+    },
+    Ok(InvokeResult::Err(err)) | Err(err) => json!({ "ok": false, "error": err }),
+  }
+}
+
+/// Binary args of batched calls refer to the blobs of the whole batch. Each
+/// call gets only the blobs it refers to, with its placeholders renumbered.
+fn call_blobs(
+  args: &mut [serde_json::Value],
+  blobs: &[Vec<u8>],
+) -> Result<Vec<Vec<u8>>, InvokeError> {
+  let mut call_blobs = Vec::new();
+
+  for arg in args {
+    let Some(index) = arg.get(INVOKE_BLOB_KEY).and_then(serde_json::Value::as_u64) else {
+      continue;
+    };
+    let blob = blobs
+      .get(index as usize)
+      .ok_or_else(|| InvokeError::invalid_argument(format!("Blob {} is out of range", index)))?;
+
+    call_blobs.push(blob.clone());
+    *arg = json!({ INVOKE_BLOB_KEY: call_blobs.len() - 1 });
+  }
+
+  Ok(call_blobs)
+}
+
+/// Runs several invokes in one round trip. Every call is dispatched through
+/// `AppExt::invoke`, so middlewares apply to each entry, and a failing entry
+/// does not affect the others.
+async fn batch(command: InvokeCommand) -> InvokeResult {
+  let calls = match invoke_arg::<Vec<BatchCall>>(&command, 0) {
+    Ok(calls) => calls,
+    Err(err) => return err,
+  };
+  let options = match invoke_arg::<Option<BatchOptions>>(&command, 1) {
+    Ok(options) => options.unwrap_or_default(),
+    Err(err) => return err,
+  };
+
+  let dispatch = |mut call: BatchCall| {
+    let (responder, receiver) = InvokeResponder::channel(command.app.clone());
+
+    match call_blobs(&mut call.args, &command.blobs) {
+      Ok(blobs) => command.app.invoke(
+        InvokeCommand {
+          app: command.app.clone(),
+          method: call.method,
+          args: call.args,
+          blobs,
+          window: command.window.clone(),
+          cancellation: command.cancellation.clone(),
+        },
+        responder,
+      ),
+      Err(err) => responder.reply(err.into()),
+    }
+
+    receiver
+  };
+
+  let mut entries = Vec::with_capacity(calls.len());
+
+  if options.mode == BatchMode::Parallel {
+    let receivers: Vec<_> = calls.into_iter().map(dispatch).collect();
+
+    for receiver in receivers {
+      entries.push(receiver.await);
+    }
+  } else {
+    for call in calls {
+      entries.push(dispatch(call).await);
+    }
+  }
+
+  let mut blobs = Vec::new();
+  let entries = entries
+    .into_iter()
+    .map(|result| {
+      batch_entry(
+        command.window.as_ref(),
+        &mut blobs,
+        result.map_err(|_| InvokeError::new("internal", "Handler dropped the request")),
+      )
+    })
+    .collect();
+
+  if blobs.is_empty() {
+    InvokeResult::json(entries)
+  } else {
+    InvokeResult::mixed(entries, blobs)
+  }
+}
+
+async fn call_reply(window: AppWindow, id: u32, reply: CallReply) -> InvokeResult {
+  window.resolve_call(
+    id,
+    if reply.ok {
+      Ok(reply.value.unwrap_or_default())
+    } else {
+      Err(
+        reply
+          .error
+          .unwrap_or_else(|| InvokeError::from("Call failed")),
+      )
+    },
+  );
+
+  ().into()
+}
+
+async fn unsubscribe(window: AppWindow, id: u32) -> InvokeResult {
+  channel::unsubscribe(&window.app(), window.id(), id).into()
+}
+
+/// Sent by `lenz/ipc` once its event listeners are installed. Events queued
+/// for the window are flushed on the event loop thread, unless another page
+/// started loading in the meantime.
+async fn ready(window: AppWindow) -> InvokeResult {
+  window
+    .app()
+    .event_loop_proxy
+    .send_event(AppWindowEvent::PageReady {
+      window_id: window.id(),
+      generation: window.page_generation(),
+    })
+    .is_ok()
+    .into()
+}
+
+async fn describe(app: App) -> InvokeResult {
+  serde_json::to_value(describe_invoke_methods(&app))
+    .map_err(|err| InvokeError::new("internal", err.to_string()))
+    .into()
+}
+
+pub fn apply(app: App) {
+  // Default timeout for methods without more specific limits.
+  if let Some(timeout_ms) = std::env::var("LENZ_INVOKE_TIMEOUT_MS")
+    .ok()
+    .and_then(|value| value.parse().ok())
+  {
+    app.set_invoke_limits(
+      "*",
+      InvokeLimits::new().with_timeout(Duration::from_millis(timeout_ms)),
+    );
+  }
+
+  async_invoke_handlers!(app, {
+    "batch" => batch: Vec<serde_json::Value>,
+    "ipc.call_reply" => call_reply: (),
+    "ipc.unsubscribe" => unsubscribe: bool,
+    "ipc.ready" => ready: bool,
+    "ipc.describe" => describe: Vec<serde_json::Value>
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn gives_each_batched_call_its_own_blobs() {
+    // `invokeBatch([{ method: 'fs.write', args: ['a.bin', new Uint8Array([1, 2])] },
+    //   { method: 'fs.write', args: ['b.bin', new Uint8Array([3])] }])`
+    let blobs = vec![vec![1, 2], vec![3]];
+    let mut first = vec![json!("a.bin"), json!({ "$blob": 0 })];
+    let mut second = vec![json!("b.bin"), json!({ "$blob": 1 })];
+
+    assert_eq!(call_blobs(&mut first, &blobs).unwrap(), vec![vec![1, 2]]);
+    assert_eq!(first, vec![json!("a.bin"), json!({ "$blob": 0 })]);
+    assert_eq!(call_blobs(&mut second, &blobs).unwrap(), vec![vec![3]]);
+    assert_eq!(second, vec![json!("b.bin"), json!({ "$blob": 0 })]);
+  }
+
+  #[test]
+  fn rejects_blobs_out_of_range() {
+    let mut args = vec![json!({ "$blob": 1 })];
+
+    assert!(call_blobs(&mut args, &[vec![1]]).is_err());
+  }
+}
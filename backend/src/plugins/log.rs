@@ -0,0 +1,38 @@
+use std::time::Instant;
+
+use crate::app::{window::AppWindowExt, App, AppExt};
+
+pub fn apply(app: App) {
+  if std::env::var("LENZ_LOG_EVENTS").is_ok() {
+    app.on("*", |event| {
+      println!(
+        "[event] {} -> {:?}: {}",
+        event.name, event.target, event.payload
+      );
+    });
+  }
+
+  if std::env::var("LENZ_LOG_INVOKE").is_err() {
+    return;
+  }
+
+  app.add_invoke_middleware("*", |command, responder, next| {
+    let method = command.method.clone();
+    let window_id = command.window.as_ref().map(|window| window.id());
+    let started_at = Instant::now();
+
+    let responder = responder.map(move |result| {
+      println!(
+        "[invoke] {} (window {:?}) -> {} in {:?}",
+        method,
+        window_id,
+        if result.is_ok() { "Ok" } else { "Err" },
+        started_at.elapsed()
+      );
+
+      result
+    });
+
+    next.run(command, responder);
+  });
+}
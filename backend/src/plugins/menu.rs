@@ -0,0 +1,123 @@
+use crate::{
+  app::{
+    close,
+    invoke::{InvokeError, InvokeResult},
+    menu::{self, Menu, MenuItem, PopupMenuItem, PopupPosition},
+    window::{AppWindow, AppWindowEvent, AppWindowExt},
+    App, AppExt,
+  },
+  async_invoke_handlers,
+};
+
+fn default_menu_bar() -> Vec<Menu> {
+  vec![
+    Menu::new(
+      "file",
+      "Arquivo",
+      vec![MenuItem::command("app.quit", "Sair", Some("CmdOrCtrl+Q"))],
+    ),
+    Menu::new(
+      "view",
+      "Exibir",
+      vec![
+        MenuItem::command("window.reload", "Recarregar", Some("CmdOrCtrl+R")),
+        MenuItem::command("window.toggle_fullscreen", "Tela cheia", Some("F11")),
+      ],
+    ),
+    Menu::new(
+      "window",
+      "Janela",
+      vec![
+        MenuItem::command("window.minimize", "Minimizar", None),
+        MenuItem::command("window.toggle_maximize", "Maximizar", None),
+        MenuItem::Separator,
+        MenuItem::command("window.close", "Fechar janela", Some("CmdOrCtrl+W")),
+      ],
+    ),
+  ]
+}
+
+/// Runs the built-in commands on the window they were selected in. Commands
+/// contributed by extensions are left to the page.
+fn execute_builtin(app: &App, window_id: u32, command: &str) {
+  let Some(window) = app.get_window(window_id) else {
+    return;
+  };
+
+  match command {
+    "app.quit" => {
+      let main_id = *app
+        .main_window_id
+        .read()
+        .expect("Main window id lock is poisoned");
+
+      if let Some(main_id) = main_id {
+        close::request_close(app.clone(), main_id);
+      }
+    }
+    "window.close" => close::request_close(app.clone(), window_id),
+    "window.reload" => window.eval("location.reload()"),
+    "window.toggle_fullscreen" => window.set_fullscreen(!window.is_fullscreen()),
+    "window.minimize" => window.set_minimized(true),
+    "window.toggle_maximize" => window.set_maximized(!window.is_maximized()),
+    _ => {}
+  }
+}
+
+/// Shows a native context menu in the calling window and resolves with the id
+/// of the selected item, or null when it is dismissed.
+async fn menu_popup(
+  window: AppWindow,
+  items: Vec<PopupMenuItem>,
+  position: Option<PopupPosition>,
+) -> InvokeResult {
+  let (reply, selected) = tokio::sync::oneshot::channel();
+
+  if window
+    .app
+    .event_loop_proxy
+    .send_event(AppWindowEvent::Popup {
+      window_id: window.id(),
+      items,
+      position,
+      reply,
+    })
+    .is_err()
+  {
+    return InvokeError::new("closed", "Event loop is closed").into();
+  }
+
+  selected.await.ok().flatten().into()
+}
+
+pub fn apply(app: App) {
+  async_invoke_handlers!(app, {
+    "menu.popup" => menu_popup: Option<String>
+  });
+
+  app.set_menu_bar(menu::DEFAULT_MENU_BAR, default_menu_bar());
+
+  muda::MenuEvent::set_event_handler(Some({
+    let app = app.clone();
+
+    move |event: muda::MenuEvent| menu::handle_menu_event(&app, event.id().as_ref())
+  }));
+
+  app.on("command.execute", {
+    let app = app.clone();
+
+    move |event| {
+      if !event.payload["extension"].is_null() {
+        return;
+      }
+
+      let (Some(&window_id), Some(command)) =
+        (event.target.first(), event.payload["command"].as_str())
+      else {
+        return;
+      };
+
+      execute_builtin(&app, window_id, command);
+    }
+  });
+}
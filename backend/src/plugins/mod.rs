@@ -3,11 +3,19 @@ use crate::app::App;
 mod app;
 mod dialog;
 mod fs;
+mod ipc;
+mod log;
+mod menu;
+mod monitor;
 mod window;
 
 pub fn apply(app: App) {
+  log::apply(app.clone());
   app::apply(app.clone());
   fs::apply(app.clone());
+  ipc::apply(app.clone());
   window::apply(app.clone());
+  monitor::apply(app.clone());
   dialog::apply(app.clone());
+  menu::apply(app.clone());
 }
@@ -0,0 +1,40 @@
+use crate::{
+  app::{
+    invoke::{InvokeError, InvokeResult},
+    monitors::{self, MonitorInfo},
+    window::{run_on_main_thread, AppWindow, AppWindowExt},
+    App, AppExt,
+  },
+  async_invoke_handlers,
+};
+
+async fn list(window: AppWindow) -> Result<Vec<MonitorInfo>, InvokeError> {
+  let app = window.app();
+
+  run_on_main_thread(&app, move || monitors::list(&window.tao_window))
+    .await
+    .ok_or_else(|| InvokeError::new("closed", "Event loop is closed"))
+}
+
+async fn monitor_list(window: AppWindow) -> InvokeResult {
+  match list(window).await {
+    Ok(monitors) => {
+      InvokeResult::json(serde_json::to_value(monitors).expect("Failed to serialize monitors"))
+    }
+    Err(err) => err.into(),
+  }
+}
+
+async fn monitor_primary(window: AppWindow) -> InvokeResult {
+  match list(window).await {
+    Ok(monitors) => monitors.into_iter().find(|monitor| monitor.primary).into(),
+    Err(err) => err.into(),
+  }
+}
+
+pub fn apply(app: App) {
+  async_invoke_handlers!(app, {
+    "monitor.list" => monitor_list: Vec<MonitorInfo>,
+    "monitor.primary" => monitor_primary: Option<MonitorInfo>
+  });
+}
@@ -27,12 +27,101 @@
     document.head.appendChild(script)
   }
 
+  // Frameless resizable windows are resized from this many pixels around the
+  // viewport. The frame is queried once `lenz/ipc` can be imported and kept up
+  // to date with `window.frame_changed`.
+  const RESIZE_BORDER = 5;
+
+  let resizeBorder = 0;
+  let resizeCursor = null;
+
+  function invoke(method, ...args) {
+    return import('lenz/ipc').then(({ invokeAsync }) => invokeAsync(method, ...args))
+  }
+
+  function updateResizeBorder({ decorated, resizable }) {
+    resizeBorder = !decorated && resizable ? RESIZE_BORDER : 0
+  }
+
+  function watchFrame() {
+    import('lenz/ipc')
+      .then(({ on }) => {
+        on('window.frame_changed', updateResizeBorder)
+
+        return Promise.all([
+          invoke('window.is_decorated', window.ID),
+          invoke('window.is_resizable', window.ID),
+        ])
+      })
+      .then(([decorated, resizable]) => updateResizeBorder({ decorated, resizable }))
+      .catch(console.error)
+  }
+
+  function resizeDirection(event) {
+    if (!resizeBorder) return null;
+
+    const vertical = event.clientY < resizeBorder
+      ? 'n'
+      : event.clientY >= window.innerHeight - resizeBorder ? 's' : ''
+    const horizontal = event.clientX < resizeBorder
+      ? 'w'
+      : event.clientX >= window.innerWidth - resizeBorder ? 'e' : ''
+
+    return vertical + horizontal || null
+  }
+
+  // Elements inside a drag region stay interactive when they are form
+  // controls, links or marked with `data-lenz-drag-region="false"`.
+  function isDragRegion(target) {
+    const region = target.closest?.('[data-lenz-drag-region]')
+
+    return region
+      && region.dataset.lenzDragRegion !== 'false'
+      && !target.closest('input, textarea, select, button, a, [contenteditable]')
+  }
+
+  document.addEventListener('mousemove', (event) => {
+    if (event.buttons) return;
+
+    const direction = resizeDirection(event)
+
+    if (direction === resizeCursor) return;
+
+    document.documentElement.style.cursor = direction ? `${direction}-resize` : ''
+    resizeCursor = direction
+  })
+
+  document.addEventListener('mousedown', (event) => {
+    if (event.button !== 0) return;
+
+    const direction = resizeDirection(event)
+
+    if (direction) {
+      event.preventDefault()
+      invoke('window.drag_resize_window', direction).catch(console.error)
+      return;
+    }
+
+    if (!isDragRegion(event.target)) return;
+
+    event.preventDefault()
+
+    if (event.detail === 2) {
+      invoke('window.is_maximized', window.ID)
+        .then((maximized) => invoke('window.set_maximized', window.ID, !maximized))
+        .catch(console.error)
+    } else {
+      invoke('window.drag_window').catch(console.error)
+    }
+  })
+
   const obs = new MutationObserver((mutations) => {
     for (const mutation of mutations) {
       for (const node of mutation.addedNodes) {
         if (node instanceof HTMLHeadElement) {
           injectImportMap()
           injectIpc()
+          watchFrame()
           obs.disconnect()
         }
       }
@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use crate::app::{resources::custom_protocol, App};
+use crate::app::{menu, resources::custom_protocol, App};
 
 use super::{ExtensionError, ExtensionManifest};
 
@@ -73,6 +73,8 @@ impl Extension {
       .unwrap()
       .extension_host
       .add_extension(self);
+
+    menu::refresh(&app);
   }
 
   pub fn deactivate(self, app: App) {
@@ -99,5 +101,7 @@ impl Extension {
       .unwrap()
       .extension_host
       .remove_extension(&self.manifest.id);
+
+    menu::refresh(&app);
   }
 }
@@ -19,6 +19,10 @@ impl ExtensionHost {
     self.extensions.get(id)
   }
 
+  pub fn extensions(&self) -> impl Iterator<Item = &Extension> {
+    self.extensions.values()
+  }
+
   pub fn search_extensions(&self) -> ExtensionsScanner {
     ExtensionsScanner::new(app_paths::extensions_search_paths().into_iter())
   }
@@ -32,6 +32,14 @@ pub struct ExtensionManifestContributesCommand {
   pub icon: Option<ExtensionIcon>,
 }
 
+/// Places a contributed command in the top-level menu with id `menu`.
+#[derive(serde::Deserialize)]
+pub struct ExtensionManifestContributesMenu {
+  pub menu: String,
+  pub command: String,
+  pub accelerator: Option<String>,
+}
+
 #[derive(serde::Deserialize)]
 pub struct ExtensionManifestContributesView {
   pub id: String,
@@ -78,6 +86,8 @@ pub struct ExtensionManifestContributes {
   pub tools: Vec<ExtensionManifestContributesTool>,
   pub providers: Vec<ExtensionManifestContributesProvider>,
   pub l10n: Vec<ExtensionManifestContributesL10n>,
+  #[serde(default)]
+  pub menus: Vec<ExtensionManifestContributesMenu>,
 }
 
 fn default_activate_on() -> HashSet<String> {
@@ -1,3 +1,5 @@
+import { on } from "lenz/ipc";
+
 export class Extension {
   constructor({
     id,
@@ -58,6 +60,34 @@ export class Extension {
   }
 }
 
+const commands = new Map();
+
+// Menu items of commands contributed by extensions emit `command.execute` to
+// the window they were selected in.
+on('command.execute', ({ command, ...context }) => {
+  const callback = commands.get(command);
+
+  if (!callback) {
+    return;
+  }
+
+  Promise.resolve()
+    .then(() => callback(context))
+    .catch(console.error);
+});
+
+export function registerCommand(id, callback) {
+  commands.set(id, callback);
+
+  return {
+    dispose() {
+      if (commands.get(id) === callback) {
+        commands.delete(id);
+      }
+    }
+  };
+}
+
 const extension_queue = [];
 let current_extension = null;
 
@@ -1,4 +1,4 @@
-import { invokeAsync, invokeSync } from "lenz/ipc";
+import { invokeAsync, invokeSync, subscribe } from "lenz/ipc";
 
 export function readFile(path) {
   return invokeAsync('fs.read', path);
@@ -8,6 +8,14 @@ export function readFileSync(path) {
   return invokeSync('fs.read', path);
 }
 
+export function readFileStream(path) {
+  return invokeAsync('fs.read_stream', path);
+}
+
+export function watch(path, callback) {
+  return subscribe('fs.watch', path, callback);
+}
+
 export function writeFile(path, data) {
   return invokeAsync('fs.write', path, data);
 }
@@ -1,10 +1,84 @@
+// Outside of a Lenz window (e.g. the UI opened in a regular browser during
+// development) IPC goes through the localhost bridge loaded from its `init.js`.
+const bridge = window.ID === undefined ? window.LENZ_BRIDGE : undefined;
+
+export function getWindowId() {
+  return window.ID ?? bridge?.windowId;
+}
+
+function ipcUrl(path) {
+  return bridge ? `${bridge.url}/ipc/${path}` : window.CUSTOM_PROTOCOL('ipc', path);
+}
+
+// The bridge only accepts requests carrying the token of the current launch.
+function ipcHeaders() {
+  const headers = { 'X-Window-Id': getWindowId() };
+
+  if (bridge) {
+    headers['X-Lenz-Token'] = bridge.token;
+  }
+
+  return headers;
+}
+
 export class InvokeError extends Error {
-  constructor(message) {
+  constructor(error) {
+    const { code = 'error', message = String(error), data = null } =
+      typeof error === 'object' && error !== null ? error : { message: error };
+
     super(message);
     this.name = 'InvokeError';
+    this.code = code;
+    this.data = data;
   }
 }
 
+export class InvokeStream extends ReadableStream {
+  constructor(id, length = null) {
+    let loaded = 0;
+    const url = ipcUrl(`stream/${id}`);
+    // Streams can only be pulled by the window they were returned to.
+    const headers = ipcHeaders();
+
+    super({
+      pull: async (controller) => {
+        const response = await fetch(url, { method: 'POST', headers });
+
+        if (response.headers.get('X-Stream-Done') === 'true') {
+          controller.close();
+          return;
+        }
+
+        if (!response.ok) {
+          controller.error(
+            new InvokeError(
+              response.headers.get('Content-Type') === 'application/json'
+                ? await response.json()
+                : await response.text()
+            )
+          );
+          return;
+        }
+
+        const chunk = new Uint8Array(await response.arrayBuffer());
+
+        loaded += chunk.byteLength;
+        this.onprogress?.({ loaded, length });
+        controller.enqueue(chunk);
+      },
+      cancel: () => fetch(`${url}/cancel`, { method: 'POST', headers }),
+    });
+
+    this.id = id;
+    this.length = length;
+    this.onprogress = null;
+  }
+}
+
+function createInvokeStream(streamId, streamLength) {
+  return new InvokeStream(Number(streamId), streamLength === null ? null : Number(streamLength));
+}
+
 const listenersMap = new Map()
 
 export function on(event, callback) {
@@ -25,15 +99,141 @@ window.__dispatch = function dispatch(event, data) {
   listenersMap.get(event)?.forEach(callback => callback(data));
 }
 
+if (bridge) {
+  const events = new EventSource(`${bridge.url}/events?window=${bridge.windowId}&token=${bridge.token}`);
+
+  events.onmessage = ({ data }) => {
+    const { name, payload } = JSON.parse(data);
+
+    window.__dispatch(name, payload);
+  };
+}
+
+const callHandlers = new Map()
+
+// Registers a handler Rust can await with `AppWindowExt::call`.
+export function handle(method, callback) {
+  callHandlers.set(method, callback);
+
+  return () => {
+    if (callHandlers.get(method) === callback) {
+      callHandlers.delete(method);
+    }
+  };
+}
+
+on('ipc.call', async ({ id, method, args }) => {
+  try {
+    const handler = callHandlers.get(method);
+
+    if (!handler) {
+      throw new InvokeError({
+        code: 'method_not_found',
+        message: `No call handler registered for method: ${method}`,
+      });
+    }
+
+    const value = await handler(...args);
+
+    await invokeAsync('ipc.call_reply', id, { ok: true, value: value ?? null });
+  } catch (err) {
+    await invokeAsync('ipc.call_reply', id, {
+      ok: false,
+      error: {
+        code: err?.code ?? 'error',
+        message: err?.message ?? String(err),
+        data: err?.data ?? null,
+      },
+    });
+  }
+});
+
+function isBinary(value) {
+  return value instanceof ArrayBuffer || ArrayBuffer.isView(value);
+}
+
+function toBytes(value) {
+  return value instanceof ArrayBuffer
+    ? new Uint8Array(value)
+    : new Uint8Array(value.buffer, value.byteOffset, value.byteLength);
+}
+
+// Moves a binary value to `blobs` and returns the `{ $blob: index }`
+// placeholder that refers to it.
+function toBlobPlaceholder(blobs, value) {
+  if (!isBinary(value)) {
+    return value;
+  }
+
+  blobs.push(toBytes(value));
+
+  return { $blob: blobs.length - 1 };
+}
+
+// Binary params are replaced by `{ $blob: index }` in the JSON frame and sent
+// as length-prefixed frames after it. `blobs` may already hold the blobs that
+// placeholders nested in the params refer to.
+function encodeMixedBody(params, blobs = []) {
+  const args = params.map((param) => toBlobPlaceholder(blobs, param));
+  const frames = [new TextEncoder().encode(JSON.stringify(args)), ...blobs];
+  const body = new Uint8Array(frames.reduce((size, frame) => size + 4 + frame.byteLength, 0));
+  const view = new DataView(body.buffer);
+  let offset = 0;
+
+  for (const frame of frames) {
+    view.setUint32(offset, frame.byteLength, true);
+    body.set(frame, offset + 4);
+    offset += 4 + frame.byteLength;
+  }
+
+  return body.buffer;
+}
+
+// Reverses `encodeMixedBody`, replacing `{ $blob }` placeholders with the
+// `ArrayBuffer` they refer to.
+function decodeMixedBody(buffer) {
+  const view = new DataView(buffer);
+  const frames = [];
+  let offset = 0;
+
+  while (offset < buffer.byteLength) {
+    const length = view.getUint32(offset, true);
+
+    frames.push(buffer.slice(offset + 4, offset + 4 + length));
+    offset += 4 + length;
+  }
+
+  const [value, ...blobs] = frames;
+
+  return JSON.parse(new TextDecoder().decode(value), (_, item) => {
+    return item !== null && typeof item === 'object' && Object.keys(item).length === 1 && typeof item.$blob === 'number'
+      ? blobs[item.$blob]
+      : item;
+  });
+}
+
+// Request ids are keyed by window on the backend, so each page load starts
+// from a random prefix to keep them apart from the ones of the previous page.
+let nextRequestId = crypto.getRandomValues(new Uint32Array(1))[0] * 2 ** 20 + 1;
+
 export function createInvokeRequest(method, ...params) {
+  return createRequest(method, params);
+}
+
+function createRequest(method, params, blobs = []) {
   let body = null;
+  const requestId = nextRequestId++;
   const headers = new Headers({
-    'X-Window-Id': window.ID
+    ...ipcHeaders(),
+    'X-Request-Id': requestId,
   });
 
-  if (params.length === 1 && params[0] instanceof ArrayBuffer) {
+  if (blobs.length === 0 && params.length === 1 && isBinary(params[0])) {
     headers.set('Content-Type', 'application/octet-stream');
     body = params[0];
+  } else if (blobs.length > 0 || params.some(isBinary)) {
+    headers.set('Content-Type', 'application/x-lenz-invoke');
+    body = encodeMixedBody(params, blobs);
   } else {
     headers.set('Content-Type', 'application/json');
     body = JSON.stringify(params);
@@ -41,16 +241,39 @@ export function createInvokeRequest(method, ...params) {
 
   return {
     method: 'POST',
-    url: window.CUSTOM_PROTOCOL('ipc', `invoke/${method}`),
+    url: ipcUrl(`invoke/${method}`),
     headers,
     body,
+    requestId,
   }
 }
 
+function cancelInvoke(requestId) {
+  return fetch(ipcUrl(`cancel/${requestId}`), {
+    method: 'POST',
+    headers: ipcHeaders(),
+  }).catch(() => { });
+}
+
+// An `AbortSignal` passed as the last param cancels the handler on the backend.
 export function invokeAsync(name, ...params) {
-  const { method, url, headers, body } = createInvokeRequest(name, ...params);
+  const signal = params.at(-1) instanceof AbortSignal ? params.pop() : undefined;
 
-  return fetch(url, { method, headers, body }).then(async (response) => {
+  return sendInvoke(createRequest(name, params), signal);
+}
+
+function sendInvoke({ method, url, headers, body, requestId }, signal) {
+  if (signal?.aborted) {
+    return Promise.reject(signal.reason);
+  }
+
+  const onAbort = () => cancelInvoke(requestId);
+
+  signal?.addEventListener('abort', onAbort, { once: true });
+
+  return fetch(url, { method, headers, body, signal }).finally(() => {
+    signal?.removeEventListener('abort', onAbort);
+  }).then(async (response) => {
     const resultType = response.headers.get('X-Invoke-Result');
 
     if (resultType === 'Err') {
@@ -64,10 +287,18 @@ export function invokeAsync(name, ...params) {
     }
 
     if (resultType === 'Ok') {
+      if (response.headers.has('X-Invoke-Stream')) {
+        return createInvokeStream(response.headers.get('X-Invoke-Stream'), response.headers.get('X-Invoke-Stream-Length'));
+      }
+
       if (response.headers.get('Content-Type') === 'application/json') {
         return response.json();
       }
 
+      if (response.headers.get('Content-Type') === 'application/x-lenz-invoke') {
+        return decodeMixedBody(await response.arrayBuffer());
+      }
+
       return response.arrayBuffer()
     }
 
@@ -77,6 +308,95 @@ export function invokeAsync(name, ...params) {
   });
 }
 
+function decodeBatchEntry(entry) {
+  if (!entry.ok) {
+    return { ok: false, error: new InvokeError(entry.error) };
+  }
+
+  if (entry.stream !== undefined) {
+    return { ok: true, value: createInvokeStream(entry.stream, entry.length) };
+  }
+
+  return { ok: true, value: entry.value };
+}
+
+// Runs `calls` (`[{ method, args }]`) in a single request. Each result is
+// `{ ok: true, value }` or `{ ok: false, error }`, in the order of `calls`.
+export async function invokeBatch(calls, { mode = 'sequential', signal } = {}) {
+  // Binary args of every call share the blobs of the batch request.
+  const blobs = [];
+  const batch = calls.map(({ method, args = [] }) => ({
+    method,
+    args: args.map((arg) => toBlobPlaceholder(blobs, arg)),
+  }));
+
+  const entries = await sendInvoke(createRequest('batch', [batch, { mode }], blobs), signal);
+
+  return entries.map(decodeBatchEntry);
+}
+
+const channelListeners = new Map();
+// Messages can arrive before the invoke that opened the channel resolves.
+const pendingChannelMessages = new Map();
+
+on('ipc.channel', ({ id, message, closed }) => {
+  const listener = channelListeners.get(id);
+
+  if (!listener) {
+    const pending = pendingChannelMessages.get(id) ?? [];
+
+    pending.push({ message, closed });
+    pendingChannelMessages.set(id, pending);
+    return;
+  }
+
+  if (closed) {
+    channelListeners.delete(id);
+    listener.onclose?.();
+    return;
+  }
+
+  listener.callback(message);
+});
+
+export class Subscription {
+  constructor(id, callback) {
+    this.id = id;
+    this.callback = callback;
+    this.onclose = null;
+  }
+
+  unsubscribe() {
+    if (!channelListeners.delete(this.id)) {
+      return Promise.resolve(false);
+    }
+
+    pendingChannelMessages.delete(this.id);
+
+    return invokeAsync('ipc.unsubscribe', this.id);
+  }
+}
+
+// Calls a method that opens a channel and forwards its messages to the
+// callback passed as the last param.
+export async function subscribe(name, ...params) {
+  const callback = params.pop();
+  const { $channel: id } = await invokeAsync(name, ...params);
+  const subscription = new Subscription(id, callback);
+
+  channelListeners.set(id, subscription);
+
+  const pending = pendingChannelMessages.get(id) ?? [];
+
+  pendingChannelMessages.delete(id);
+
+  for (const { message, closed } of pending) {
+    window.__dispatch('ipc.channel', { id, message, closed });
+  }
+
+  return subscription;
+}
+
 export function invokeSync(name, ...params) {
   const { method, url, headers, body } = createInvokeRequest(name, ...params);
 
@@ -108,6 +428,10 @@ export function invokeSync(name, ...params) {
   }
 
   if (resultType === 'Ok') {
+    if (xhr.getResponseHeader('X-Invoke-Stream') !== null) {
+      return createInvokeStream(xhr.getResponseHeader('X-Invoke-Stream'), xhr.getResponseHeader('X-Invoke-Stream-Length'));
+    }
+
     if (xhr.getResponseHeader('Content-Type') === 'application/json') {
       if (typeof xhr.response === 'string') {
         return JSON.parse(xhr.response)
@@ -115,8 +439,17 @@ export function invokeSync(name, ...params) {
       return JSON.parse(new TextDecoder().decode(new Uint8Array(xhr.response)));
     }
 
+    if (xhr.getResponseHeader('Content-Type') === 'application/x-lenz-invoke' && xhr.response instanceof ArrayBuffer) {
+      return decodeMixedBody(xhr.response);
+    }
+
     return xhr.response;
   }
 
   throw new Error('Invalid Invoke Result');
-}
\ No newline at end of file
+}
+// Events sent to this window are queued by the backend until the listeners
+// above are installed.
+if (window.ID !== undefined) {
+  invokeAsync('ipc.ready').catch((err) => console.error('Failed to signal page ready', err));
+}
@@ -0,0 +1,22 @@
+import { invokeAsync } from "lenz/ipc";
+
+// Shows a native context menu and resolves with the id of the selected item,
+// or null when it is dismissed. Items are objects with a `type`:
+//
+// - `item`: id, label, disabled and accelerator (e.g. "CmdOrCtrl+C")
+// - `checkbox`: id, label, checked and disabled
+// - `submenu`: label, items and disabled
+// - `separator`
+//
+// `position` is `{ x, y }` in CSS pixels from the top-left corner of the page.
+// Without it, the menu opens at the cursor.
+export function popupMenu(items, position = null) {
+  return invokeAsync('menu.popup', items, position);
+}
+
+// Shows the menu for a `contextmenu` event in place of the browser one.
+export function showContextMenu(event, items) {
+  event.preventDefault();
+
+  return popupMenu(items, { x: event.clientX, y: event.clientY });
+}
@@ -0,0 +1,11 @@
+import { invokeSync } from "lenz/ipc";
+
+// Connected monitors. Positions and sizes are physical pixels in the desktop
+// space shared by all monitors; `index` identifies a monitor in other methods.
+export function getMonitors() {
+  return invokeSync('monitor.list');
+}
+
+export function getPrimaryMonitor() {
+  return invokeSync('monitor.primary');
+}
@@ -1,4 +1,4 @@
-import { invokeSync } from "lenz/ipc";
+import { getWindowId as getIpcWindowId, handle, invokeAsync, invokeSync, on } from "lenz/ipc";
 
 export function emitToLabel(label, event, payload) {
   invokeSync('window.emit_label', label, event, payload);
@@ -9,7 +9,128 @@ export function emitToAll(event, payload) {
 }
 
 export function getWindowId() {
-  return window.ID;
+  return getIpcWindowId();
+}
+
+// Lifecycle events of the current window. Sizes and positions are physical
+// pixels, with the `scale_factor` to convert them. Returns an unsubscribe function.
+export function onWindowEvent(event, callback) {
+  return on(`window.${event}`, callback);
+}
+
+export function onFocused(callback) {
+  return onWindowEvent('focused', callback);
+}
+
+export function onBlurred(callback) {
+  return onWindowEvent('blurred', callback);
+}
+
+export function onResized(callback) {
+  return onWindowEvent('resized', callback);
+}
+
+export function onMoved(callback) {
+  return onWindowEvent('moved', callback);
+}
+
+export function onScaleFactorChanged(callback) {
+  return onWindowEvent('scale_factor_changed', callback);
+}
+
+export function onThemeChanged(callback) {
+  return onWindowEvent('theme_changed', callback);
+}
+
+export function onMinimized(callback) {
+  return onWindowEvent('minimized', callback);
+}
+
+export function onMaximized(callback) {
+  return onWindowEvent('maximized', callback);
+}
+
+export function onRestored(callback) {
+  return onWindowEvent('restored', callback);
+}
+
+// Called with `{ decorated, resizable }` when either changes.
+export function onFrameChanged(callback) {
+  return onWindowEvent('frame_changed', callback);
+}
+
+// Passed to close listeners, which can await a confirmation before calling
+// `preventDefault` to keep the window open.
+export class CloseRequestEvent {
+  constructor(type) {
+    this.type = type;
+    this.defaultPrevented = false;
+  }
+
+  preventDefault() {
+    this.defaultPrevented = true;
+  }
+}
+
+const closeRequestedListeners = new Set();
+const beforeQuitListeners = new Set();
+
+async function runCloseListeners(type, listeners) {
+  const event = new CloseRequestEvent(type);
+
+  for (const listener of [...listeners]) {
+    await listener(event);
+  }
+
+  return { prevented: event.defaultPrevented };
+}
+
+handle('window.close_requested', () => runCloseListeners('close_requested', closeRequestedListeners));
+handle('app.before_quit', () => runCloseListeners('before_quit', beforeQuitListeners));
+
+// The user is closing this window. Returns an unsubscribe function.
+export function onCloseRequested(callback) {
+  closeRequestedListeners.add(callback);
+
+  return () => closeRequestedListeners.delete(callback);
+}
+
+// The user is closing the main window, which quits the app. Every open window
+// is asked and any of them can prevent it. Returns an unsubscribe function.
+export function onBeforeQuit(callback) {
+  beforeQuitListeners.add(callback);
+
+  return () => beforeQuitListeners.delete(callback);
+}
+
+// Opens a new window. Options: url, label, title, width, height, x, y,
+// decorations, resizable, transparent, visible, parent (a window id), modal,
+// devtools, restoreState (a key to remember the window geometry under) and
+// menuBar ("main" shows the app menu bar).
+//
+// In frameless windows, elements with `data-lenz-drag-region` move the window
+// and maximize it on double-click, and the edges of the page resize it.
+//
+// A child stays on top of its parent and closes with it. A modal child also
+// blocks input to the parent until it closes.
+export async function createWindow(options = {}) {
+  return new Window(await invokeAsync('window.create', options));
+}
+
+// Opens a modal child of the current window, like a native dialog.
+export function createModal(options = {}) {
+  return createWindow({ parent: getWindowId(), ...options, modal: true });
+}
+
+// Moves or resizes the current window while the mouse button is held, for
+// title bars that need more than `data-lenz-drag-region`. Call from `mousedown`.
+export function startDragging() {
+  return invokeAsync('window.drag_window');
+}
+
+// `direction` is the edge or corner to resize from: n, ne, e, se, s, sw, w or nw.
+export function startResizing(direction) {
+  return invokeAsync('window.drag_resize_window', direction);
 }
 
 export function getAllWindows() {
@@ -29,6 +150,10 @@ export class Window {
     invokeSync('window.close', this.id);
   }
 
+  call(method, ...args) {
+    return invokeAsync('window.call', this.id, method, args);
+  }
+
   show() {
     invokeSync('window.set_visible', this.id, true);
   }
@@ -44,6 +169,127 @@ export class Window {
   set title(title) {
     invokeSync('window.set_title', this.id, title);
   }
+
+  // Sizes and positions are physical pixels.
+  getSize() {
+    return invokeSync('window.get_size', this.id);
+  }
+
+  setSize(width, height) {
+    invokeSync('window.set_size', this.id, width, height);
+  }
+
+  getPosition() {
+    return invokeSync('window.get_position', this.id);
+  }
+
+  setPosition(x, y) {
+    invokeSync('window.set_position', this.id, x, y);
+  }
+
+  // Pass `null` to remove the constraint.
+  setMinSize(size) {
+    invokeSync('window.set_min_size', this.id, size);
+  }
+
+  setMaxSize(size) {
+    invokeSync('window.set_max_size', this.id, size);
+  }
+
+  isMaximized() {
+    return invokeSync('window.is_maximized', this.id);
+  }
+
+  maximize() {
+    invokeSync('window.set_maximized', this.id, true);
+  }
+
+  unmaximize() {
+    invokeSync('window.set_maximized', this.id, false);
+  }
+
+  toggleMaximize() {
+    invokeSync('window.set_maximized', this.id, !this.isMaximized());
+  }
+
+  isMinimized() {
+    return invokeSync('window.is_minimized', this.id);
+  }
+
+  minimize() {
+    invokeSync('window.set_minimized', this.id, true);
+  }
+
+  unminimize() {
+    invokeSync('window.set_minimized', this.id, false);
+  }
+
+  isFullscreen() {
+    return invokeSync('window.is_fullscreen', this.id);
+  }
+
+  setFullscreen(fullscreen) {
+    invokeSync('window.set_fullscreen', this.id, fullscreen);
+  }
+
+  setAlwaysOnTop(alwaysOnTop) {
+    invokeSync('window.set_always_on_top', this.id, alwaysOnTop);
+  }
+
+  isResizable() {
+    return invokeSync('window.is_resizable', this.id);
+  }
+
+  setResizable(resizable) {
+    invokeSync('window.set_resizable', this.id, resizable);
+  }
+
+  isDecorated() {
+    return invokeSync('window.is_decorated', this.id);
+  }
+
+  setDecorations(decorations) {
+    invokeSync('window.set_decorations', this.id, decorations);
+  }
+
+  isVisible() {
+    return invokeSync('window.is_visible', this.id);
+  }
+
+  isFocused() {
+    return invokeSync('window.is_focused', this.id);
+  }
+
+  focus() {
+    invokeSync('window.focus', this.id);
+  }
+
+  currentMonitor() {
+    return invokeSync('window.current_monitor', this.id);
+  }
+
+  // Centers on the monitor with the given index, or on the current one.
+  center(monitor) {
+    invokeSync('window.center', this.id, monitor ?? null);
+  }
+
+  moveToMonitor(monitor) {
+    invokeSync('window.move_to_monitor', this.id, monitor);
+  }
+
+  getParent() {
+    const id = invokeSync('window.get_parent', this.id);
+
+    return id === null ? null : new Window(id);
+  }
+
+  getChildren() {
+    return invokeSync('window.get_children', this.id).map(id => new Window(id));
+  }
+}
+
+export function getCurrentWindow() {
+  return new Window(getWindowId());
 }
 
 export function getWindowsByLabel(label) {